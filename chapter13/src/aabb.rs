@@ -0,0 +1,114 @@
+use crate::vec3::{Vec3, Point};
+use crate::ray::{Ray};
+
+/*
+    Axis-aligned bounding box, used to skip objects a ray cannot hit
+*/
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    pub minimum: Point,
+    pub maximum: Point,
+}
+
+impl Aabb {
+
+    // Box spanning the given points; flat boxes are padded so rays never slip between the slabs
+    pub fn from_points(points: &[Point]) -> Self {
+        let mut minimum = Vec3(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut maximum = Vec3(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for p in points {
+            minimum = Vec3(minimum.0.min(p.0), minimum.1.min(p.1), minimum.2.min(p.2));
+            maximum = Vec3(maximum.0.max(p.0), maximum.1.max(p.1), maximum.2.max(p.2));
+        }
+        Self{minimum, maximum}.padded()
+    }
+
+    pub fn surrounding(a: &Aabb, b: &Aabb) -> Self {
+        Self{
+            minimum: Vec3(a.minimum.0.min(b.minimum.0), a.minimum.1.min(b.minimum.1), a.minimum.2.min(b.minimum.2)),
+            maximum: Vec3(a.maximum.0.max(b.maximum.0), a.maximum.1.max(b.maximum.1), a.maximum.2.max(b.maximum.2)),
+        }
+    }
+
+    fn padded(self) -> Self {
+        let delta = 1e-4;
+        let mut minimum = self.minimum;
+        let mut maximum = self.maximum;
+        if maximum.0 - minimum.0 < delta {minimum.0 -= delta / 2.0; maximum.0 += delta / 2.0;}
+        if maximum.1 - minimum.1 < delta {minimum.1 -= delta / 2.0; maximum.1 += delta / 2.0;}
+        if maximum.2 - minimum.2 < delta {minimum.2 -= delta / 2.0; maximum.2 += delta / 2.0;}
+        Self{minimum, maximum}
+    }
+
+    pub fn centroid(&self) -> Point {
+        0.5 * (self.minimum + self.maximum)
+    }
+
+    // Index of the axis along which the box is longest
+    pub fn longest_axis(&self) -> usize {
+        let d = self.maximum - self.minimum;
+        if d.0 > d.1 && d.0 > d.2 {0} else if d.1 > d.2 {1} else {2}
+    }
+
+    // Slab test: intersects the ray's parameter interval with each pair of axis planes
+    pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> bool {
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+        for a in 0..3 {
+            let inv_d = 1.0 / r.direction[a];
+            let mut t0 = (self.minimum[a] - r.origin[a]) * inv_d;
+            let mut t1 = (self.maximum[a] - r.origin[a]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // Written so that a NaN from 0 * inf leaves the interval unchanged
+            t_min = if t0 > t_min {t0} else {t_min};
+            t_max = if t1 < t_max {t1} else {t_max};
+            if t_max < t_min {
+                return false
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> Aabb {
+        Aabb{minimum: Vec3(0.0, 0.0, 0.0), maximum: Vec3(1.0, 1.0, 1.0)}
+    }
+
+    fn ray(origin: Point, direction: Vec3) -> Ray {
+        Ray{origin, direction, wavelength: None, time: 0.0}
+    }
+
+    #[test]
+    fn ray_through_the_middle_hits() {
+        assert!(unit_box().hit(&ray(Vec3(0.5, 0.5, -1.0), Vec3(0.0, 0.0, 1.0)), 0.0, f64::INFINITY));
+        assert!(!unit_box().hit(&ray(Vec3(0.5, 0.5, -1.0), Vec3(0.0, 0.0, -1.0)), 0.0, f64::INFINITY));
+        assert!(!unit_box().hit(&ray(Vec3(0.5, 0.5, -1.0), Vec3(0.0, 0.0, 1.0)), 0.0, 0.5));
+    }
+
+    #[test]
+    fn parallel_ray_hits_only_between_the_slabs() {
+        assert!(unit_box().hit(&ray(Vec3(0.5, 0.5, -1.0), Vec3(0.0, 0.0, 1.0)), 0.0, f64::INFINITY));
+        assert!(!unit_box().hit(&ray(Vec3(1.5, 0.5, -1.0), Vec3(0.0, 0.0, 1.0)), 0.0, f64::INFINITY));
+        assert!(!unit_box().hit(&ray(Vec3(0.5, -0.5, -1.0), Vec3(0.0, 0.0, 1.0)), 0.0, f64::INFINITY));
+    }
+
+    #[test]
+    fn edge_on_ray_in_a_face_plane_hits() {
+        // 0 * inf is NaN on the x axis, which must not reject the ray
+        assert!(unit_box().hit(&ray(Vec3(0.0, 0.5, -1.0), Vec3(0.0, 0.0, 1.0)), 0.0, f64::INFINITY));
+        assert!(unit_box().hit(&ray(Vec3(1.0, 1.0, -1.0), Vec3(0.0, 0.0, 1.0)), 0.0, f64::INFINITY));
+    }
+
+    #[test]
+    fn ray_through_a_corner_hits() {
+        assert!(unit_box().hit(&ray(Vec3(2.0, 2.0, 2.0), Vec3(-1.0, -1.0, -1.0)), 0.0, f64::INFINITY));
+        assert!(unit_box().hit(&ray(Vec3(2.0, 0.0, 1.0), Vec3(-1.0, 1.0, 0.0)), 0.0, f64::INFINITY));
+        assert!(!unit_box().hit(&ray(Vec3(2.0, 0.0, 1.0), Vec3(-1.0, 1.01, 0.0)), 0.0, f64::INFINITY));
+    }
+}
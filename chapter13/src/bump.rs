@@ -0,0 +1,89 @@
+use std::rc::{Rc};
+
+use crate::ray::{Ray};
+use crate::vec3::{Vec3, Color};
+use crate::hittable::{HitRecord};
+use crate::material::{Material};
+use crate::texture::{Texture};
+use crate::spectrum::{SampledWavelengths, SampledSpectrum};
+use crate::color::{luminance};
+
+/*
+    Normal and bump mapping: perturb the shading normal seen by another material, while the
+    geometric normal keeps deciding front_face and where scattered rays start
+*/
+
+pub enum Perturbation {
+    // Tangent-space normals encoded as colours, (x, y, z) = 2 rgb - 1, with x along the tangent
+    NormalMap{map: Rc<dyn Texture>, strength: f64},
+    // Heights from the luminance of a scalar texture, such as NoiseTexture
+    BumpMap{height: Rc<dyn Texture>, scale: f64},
+}
+
+pub struct Bumped {
+    pub material: Rc<dyn Material>,
+    pub perturbation: Perturbation,
+}
+
+// Step for the finite-difference height gradient, in texture coordinates and in world units
+const BUMP_DELTA: f64 = 1e-3;
+// Distance scattered rays start from the surface, along the geometric normal
+const RAY_OFFSET: f64 = 1e-4;
+
+impl Bumped {
+
+    fn shading_normal(&self, rec: &HitRecord) -> Vec3 {
+        let n = rec.normal;
+        let t = rec.tangent;
+        let b = n.cross(t);
+        match &self.perturbation {
+            Perturbation::NormalMap{map, strength} => {
+                let c = map.value(rec.u, rec.v, &rec.p);
+                let (x, y, z) = (2.0 * c.0 - 1.0, 2.0 * c.1 - 1.0, 2.0 * c.2 - 1.0);
+                *strength * x * t + *strength * y * b + z * n
+            }
+            Perturbation::BumpMap{height, scale} => {
+                let h = |u: f64, v: f64, p| luminance(height.value(u, v, &p));
+                let h0 = h(rec.u, rec.v, rec.p);
+                let dh_du = (h(rec.u + BUMP_DELTA, rec.v, rec.p + BUMP_DELTA * t) - h0) / BUMP_DELTA;
+                let dh_dv = (h(rec.u, rec.v + BUMP_DELTA, rec.p + BUMP_DELTA * b) - h0) / BUMP_DELTA;
+                n - *scale * (dh_du * t + dh_dv * b)
+            }
+        }
+    }
+
+    fn perturb(&self, rec: &HitRecord) -> HitRecord {
+        let n = self.shading_normal(rec);
+        // A normal tilted past the geometric surface would shade the wrong side
+        if n.is_near_zero() || n.dot(rec.geometric_normal) <= 0.0 {
+            return rec.clone()
+        }
+        let normal = n.unit_vector();
+        let tangent = (rec.tangent - rec.tangent.dot(normal) * normal).unit_vector();
+        HitRecord{normal, tangent, ..rec.clone()}
+    }
+
+    fn offset(rec: &HitRecord, scattered: Ray) -> Ray {
+        let side = if scattered.direction.dot(rec.geometric_normal) >= 0.0 {1.0} else {-1.0};
+        Ray{origin: rec.p + side * RAY_OFFSET * rec.geometric_normal, ..scattered}
+    }
+}
+
+impl Material for Bumped {
+
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        self.material.scatter(r_in, &self.perturb(rec)).map(|(scattered, attenuation)| (Bumped::offset(rec, scattered), attenuation))
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.material.emitted(&self.perturb(rec))
+    }
+
+    fn scatter_spectral(&self, r_in: &Ray, rec: &HitRecord, lambdas: &mut SampledWavelengths) -> Option<(Ray, SampledSpectrum)> {
+        self.material.scatter_spectral(r_in, &self.perturb(rec), lambdas).map(|(scattered, attenuation)| (Bumped::offset(rec, scattered), attenuation))
+    }
+
+    fn emitted_spectral(&self, rec: &HitRecord, lambdas: &SampledWavelengths) -> SampledSpectrum {
+        self.material.emitted_spectral(&self.perturb(rec), lambdas)
+    }
+}
@@ -0,0 +1,105 @@
+use crate::ray::{Ray};
+use crate::aabb::{Aabb};
+use crate::hittable::{HitRecord, Hittable};
+
+/*
+    Bounding volume hierarchy over objects with bounding boxes
+*/
+pub struct BvhNode {
+    left: Box<dyn Hittable>,
+    right: Option<Box<dyn Hittable>>,
+    bbox: Aabb,
+}
+
+impl BvhNode {
+
+    // Splits the objects at the median centroid along the longest axis of their centroids.
+    // Fails if an object has no bounding box or the list is empty, as there is nothing to build a
+    // node from; unbounded objects belong beside the tree, as in SceneNode::flatten.
+    pub fn new(objects: Vec<Box<dyn Hittable>>) -> Result<Self, String> {
+        if objects.is_empty() {
+            return Err("a BVH needs at least one object".to_string())
+        }
+        let mut boxed = Vec::with_capacity(objects.len());
+        for o in objects {
+            let bbox = o.bounding_box().ok_or("a BVH needs objects with bounding boxes")?;
+            boxed.push((bbox, o));
+        }
+        Ok(BvhNode::build(&mut boxed))
+    }
+
+    fn build(objects: &mut Vec<(Aabb, Box<dyn Hittable>)>) -> Self {
+        let bbox = objects.iter().skip(1).fold(objects[0].0, |b, (o, _)| Aabb::surrounding(&b, o));
+        if objects.len() <= 2 {
+            let right = if objects.len() == 2 {objects.pop().map(|(_, o)| o)} else {None};
+            let left = objects.pop().map(|(_, o)| o).unwrap();
+            return Self{left, right, bbox}
+        }
+
+        let centroids: Vec<_> = objects.iter().map(|(b, _)| b.centroid()).collect();
+        let axis = Aabb::from_points(&centroids).longest_axis();
+        objects.sort_by(|(a, _), (b, _)| a.centroid()[axis].total_cmp(&b.centroid()[axis]));
+
+        let mut upper = objects.split_off(objects.len() / 2);
+        let left: Box<dyn Hittable> = Box::new(BvhNode::build(objects));
+        let right: Box<dyn Hittable> = Box::new(BvhNode::build(&mut upper));
+        Self{left, right: Some(right), bbox}
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        if !self.bbox.hit(r, t_min, t_max) {
+            return None
+        }
+        let hit_left = self.left.hit(r, t_min, t_max);
+        let closest = hit_left.as_ref().map_or(t_max, |h| h.t);
+        let hit_right = self.right.as_ref().and_then(|o| o.hit(r, t_min, closest));
+        hit_right.or(hit_left)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::{Rc};
+    use super::*;
+    use crate::vec3::{Vec3};
+    use crate::hittable::{Sphere};
+    use crate::planar::{Plane};
+    use crate::material::{Material, Lambertian};
+
+    fn material() -> Rc<dyn Material> {
+        Rc::new(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})
+    }
+
+    #[test]
+    fn empty_and_unbounded_objects_are_rejected() {
+        assert!(BvhNode::new(Vec::new()).is_err());
+        let plane = Box::new(Plane::new(Vec3(0.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0), material()));
+        let sphere = Box::new(Sphere{center: Vec3(0.0, 0.0, 0.0), radius: 1.0, material: material()});
+        assert!(BvhNode::new(vec![sphere, plane]).is_err());
+    }
+
+    #[test]
+    fn nan_centroids_do_not_panic() {
+        let objects: Vec<Box<dyn Hittable>> = [0.0, f64::NAN, 2.0, f64::NAN, 4.0].iter()
+            .map(|&x| -> Box<dyn Hittable> {Box::new(Sphere{center: Vec3(x, 0.0, 0.0), radius: 0.5, material: material()})})
+            .collect();
+        assert!(BvhNode::new(objects).is_ok());
+    }
+
+    #[test]
+    fn finds_the_closest_hit() {
+        let objects: Vec<Box<dyn Hittable>> = (0..8)
+            .map(|i| -> Box<dyn Hittable> {Box::new(Sphere{center: Vec3(0.0, 0.0, -3.0 * i as f64), radius: 1.0, material: material()})})
+            .collect();
+        let bvh = BvhNode::new(objects).unwrap();
+        let r = Ray{origin: Vec3(0.0, 0.0, -30.0), direction: Vec3(0.0, 0.0, 1.0), wavelength: None, time: 0.0};
+        let rec = bvh.hit(&r, 0.0, f64::INFINITY).unwrap();
+        assert!((rec.t - 8.0).abs() < 1e-9);
+    }
+}
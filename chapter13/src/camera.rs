@@ -1,5 +1,6 @@
 use crate::vec3::{Point, Vec3};
 use crate::ray::{Ray};
+use rand::prelude::*;
 
 pub struct Camera {
     origin: Point,
@@ -8,8 +9,10 @@ pub struct Camera {
     vertical: Vec3,
     u: Vec3,
     v: Vec3,
-    w: Vec3,
     lens_radius: f64,
+    // Shutter open and close times; rays are spread evenly between them
+    time0: f64,
+    time1: f64,
 }
 
 impl Camera {
@@ -28,17 +31,22 @@ impl Camera {
         let horizontal = focus_dist * viewport_width * u;
         let vertical = focus_dist * viewport_height * v;
         Self {
-            origin: origin,
-            horizontal: horizontal,
-            vertical: vertical,
+            origin,
+            horizontal,
+            vertical,
             lower_left_corner: origin - horizontal/2.0 - vertical/2.0 - focus_dist * w,
-            u: u,
-            v: v,
-            w: w,
-            lens_radius: aperture / 2.0
+            u,
+            v,
+            lens_radius: aperture / 2.0,
+            time0: 0.0,
+            time1: 0.0,
         }
     }
 
+    pub fn with_shutter(self, time0: f64, time1: f64) -> Self {
+        Self{time0, time1, ..self}
+    }
+
     pub fn get_ray(&self, s: f64, t: f64) -> Ray {
         let rd = self.lens_radius * Vec3::random_in_unit_disk();
         let offset = self.u * rd.0 + self.v * rd.1;
@@ -46,6 +54,8 @@ impl Camera {
         Ray {
             origin: self.origin + offset,
             direction: self.lower_left_corner + s*self.horizontal + t*self.vertical - self.origin - offset,
+            wavelength: None,
+            time: if self.time1 > self.time0 {rand::thread_rng().gen_range(self.time0..self.time1)} else {self.time0},
         }
     }
 }
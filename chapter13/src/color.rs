@@ -9,6 +9,16 @@ fn clamp(v: f64, min: f64, max: f64) -> f64 {
     if v < min {min} else if v > max {max} else {v}
 }
 
+pub fn luminance(color: Color) -> f64 {
+    0.2126 * color.0 + 0.7152 * color.1 + 0.0722 * color.2
+}
+
+// Decodes sRGB-encoded values, as 8-bit colour images store them, to linear
+pub fn srgb_to_linear(color: Color) -> Color {
+    let decode = |c: f64| if c <= 0.04045 {c / 12.92} else {((c + 0.055) / 1.055).powf(2.4)};
+    Vec3(decode(color.0), decode(color.1), decode(color.2))
+}
+
 pub fn write_color(out: &mut dyn Write, color: &Color, samples_per_pixel: i64) {
     let scale = 1.0 / samples_per_pixel as f64;
     let corrected_color = Vec3((color.0 * scale).sqrt(), (color.1 * scale).sqrt(), (color.2 * scale).sqrt());
@@ -16,4 +26,13 @@ pub fn write_color(out: &mut dyn Write, color: &Color, samples_per_pixel: i64) {
         ((256.0 * clamp(c, 0.0, 0.999)) as i64).to_string()
     };
     writeln!(out, "{} {} {}", clamped(corrected_color.0), clamped(corrected_color.1), clamped(corrected_color.2)).unwrap();
-}
\ No newline at end of file
+}
+
+pub fn write_gray_header(out: &mut dyn Write, width: i64, height: i64) {
+    writeln!(out, "P2\n{} {}\n255", width, height).unwrap();
+}
+
+// Linear greyscale value in [0, 1], e.g. a matte
+pub fn write_gray(out: &mut dyn Write, value: f64) {
+    writeln!(out, "{}", (256.0 * clamp(value, 0.0, 0.999)) as i64).unwrap();
+}
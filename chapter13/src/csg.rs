@@ -0,0 +1,176 @@
+use crate::ray::{Ray};
+use crate::aabb::{Aabb};
+use crate::vec3::{Vec3};
+use crate::hittable::{HitRecord, Hittable, Interval};
+
+/*
+    Constructive solid geometry: boolean combinations of closed objects
+*/
+
+#[derive(Clone, Copy)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    // a with b carved out of it
+    Difference,
+}
+
+impl CsgOp {
+    fn inside(&self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b,
+        }
+    }
+}
+
+// Both operands must be closed. Surfaces keep the materials and uvs of the operand they come
+// from, so the walls of a hole carved by b look like b.
+pub struct Csg {
+    pub op: CsgOp,
+    pub a: Box<dyn Hittable>,
+    pub b: Box<dyn Hittable>,
+}
+
+impl Hittable for Csg {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        // Finding the intervals walks every surface of both operands, so skip rays that miss
+        if let Some(bbox) = self.bounding_box() {
+            if !bbox.hit(r, t_min, t_max) {
+                return None
+            }
+        }
+        self.intervals(r).into_iter()
+            .flat_map(|i| vec![i.enter, i.exit])
+            .find(|rec| rec.t >= t_min && rec.t <= t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let (a, b) = (self.a.bounding_box(), self.b.bounding_box());
+        match self.op {
+            CsgOp::Union => Some(Aabb::surrounding(&a?, &b?)),
+            // The overlap of the two boxes, or whichever there is
+            CsgOp::Intersection => match (a, b) {
+                (Some(a), Some(b)) => {
+                    let minimum = Vec3(a.minimum.0.max(b.minimum.0), a.minimum.1.max(b.minimum.1), a.minimum.2.max(b.minimum.2));
+                    let maximum = Vec3(a.maximum.0.min(b.maximum.0), a.maximum.1.min(b.maximum.1), a.maximum.2.min(b.maximum.2));
+                    Some(Aabb::from_points(&[minimum, maximum]))
+                },
+                (a, b) => a.or(b),
+            },
+            CsgOp::Difference => a,
+        }
+    }
+
+    // Sweeps the boundaries of both operands along the ray, tracking whether it is inside each,
+    // and keeps those where the combination changes. Every record's normal already faces the ray,
+    // so only front_face needs setting to say whether the result is entered or left there.
+    fn intervals(&self, r: &Ray) -> Vec<Interval> {
+        let mut events: Vec<(HitRecord, bool)> = Vec::new();
+        for (object, is_a) in [(&self.a, true), (&self.b, false)] {
+            for Interval{enter, exit} in object.intervals(r) {
+                events.push((enter, is_a));
+                events.push((exit, is_a));
+            }
+        }
+        events.sort_by(|(x, _), (y, _)| x.t.total_cmp(&y.t));
+
+        let mut intervals = Vec::new();
+        let (mut in_a, mut in_b) = (false, false);
+        let mut enter: Option<HitRecord> = None;
+        for (rec, is_a) in events {
+            let before = self.op.inside(in_a, in_b);
+            if is_a {
+                in_a = rec.front_face;
+            } else {
+                in_b = rec.front_face;
+            }
+            let after = self.op.inside(in_a, in_b);
+            if after && !before {
+                enter = Some(HitRecord{front_face: true, ..rec});
+            } else if before && !after {
+                if let Some(enter) = enter.take() {
+                    intervals.push(Interval{enter, exit: HitRecord{front_face: false, ..rec}});
+                }
+            }
+        }
+        intervals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::{Rc};
+    use super::*;
+    use crate::hittable::{Sphere};
+    use crate::material::{Lambertian};
+
+    // Unit spheres centred at x = -0.5 and x = 0.5, overlapping between them
+    fn csg(op: CsgOp) -> Csg {
+        let material = Rc::new(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)});
+        Csg{
+            op,
+            a: Box::new(Sphere{center: Vec3(-0.5, 0.0, 0.0), radius: 1.0, material: material.clone()}),
+            b: Box::new(Sphere{center: Vec3(0.5, 0.0, 0.0), radius: 1.0, material}),
+        }
+    }
+
+    fn ray(x: f64, direction: f64) -> Ray {
+        Ray{origin: Vec3(x, 0.0, 0.0), direction: Vec3(direction, 0.0, 0.0), wavelength: None, time: 0.0}
+    }
+
+    // Successive hits along the ray as (t, entering)
+    fn hits(csg: &Csg, r: &Ray) -> Vec<(f64, bool)> {
+        let mut hits = Vec::new();
+        let mut t_min = 0.0;
+        while let Some(rec) = csg.hit(r, t_min, f64::INFINITY) {
+            assert!(rec.normal.dot(r.direction) < 0.0, "normal must face the ray");
+            hits.push((rec.t, rec.front_face));
+            t_min = rec.t + 1e-9;
+        }
+        hits
+    }
+
+    fn assert_hits(got: Vec<(f64, bool)>, expected: &[(f64, bool)]) {
+        assert_eq!(got.len(), expected.len(), "{:?}", got);
+        for ((t, entering), (expected_t, expected_entering)) in got.iter().zip(expected) {
+            assert!((t - expected_t).abs() < 1e-9 && entering == expected_entering, "{:?}", got);
+        }
+    }
+
+    #[test]
+    fn union() {
+        assert_hits(hits(&csg(CsgOp::Union), &ray(-5.0, 1.0)), &[(3.5, true), (6.5, false)]);
+        // From inside both operands only the far side of the union is left
+        assert_hits(hits(&csg(CsgOp::Union), &ray(0.0, 1.0)), &[(1.5, false)]);
+    }
+
+    #[test]
+    fn intersection() {
+        assert_hits(hits(&csg(CsgOp::Intersection), &ray(-5.0, 1.0)), &[(4.5, true), (5.5, false)]);
+        assert_hits(hits(&csg(CsgOp::Intersection), &ray(0.0, -1.0)), &[(0.5, false)]);
+        // Inside a alone the ray is outside the intersection until it reaches b
+        assert_hits(hits(&csg(CsgOp::Intersection), &ray(-1.0, 1.0)), &[(0.5, true), (1.5, false)]);
+    }
+
+    #[test]
+    fn difference() {
+        // The hole's wall is b's surface, seen from inside b
+        assert_hits(hits(&csg(CsgOp::Difference), &ray(-5.0, 1.0)), &[(3.5, true), (4.5, false)]);
+        assert_hits(hits(&csg(CsgOp::Difference), &ray(5.0, -1.0)), &[(5.5, true), (6.5, false)]);
+        assert_hits(hits(&csg(CsgOp::Difference), &ray(-1.0, 1.0)), &[(0.5, false)]);
+        // Inside both operands is inside the hole, and going on through a finds nothing more
+        assert_hits(hits(&csg(CsgOp::Difference), &ray(0.0, 1.0)), &[]);
+        assert_hits(hits(&csg(CsgOp::Difference), &ray(0.0, -1.0)), &[(0.5, true), (1.5, false)]);
+    }
+
+    #[test]
+    fn rays_outside_the_box_miss() {
+        let r = Ray{origin: Vec3(-5.0, 2.0, 0.0), direction: Vec3(1.0, 0.0, 0.0), wavelength: None, time: 0.0};
+        for op in [CsgOp::Union, CsgOp::Intersection, CsgOp::Difference] {
+            assert!(csg(op).hit(&r, 0.0, f64::INFINITY).is_none());
+        }
+        assert!(csg(CsgOp::Union).hit(&ray(-5.0, 1.0), 0.0, 3.0).is_none());
+    }
+}
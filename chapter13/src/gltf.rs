@@ -0,0 +1,505 @@
+use std::rc::{Rc};
+use std::collections::{HashMap};
+use std::path::{Path};
+use std::fs;
+use std::io;
+
+use crate::vec3::{Vec3, Point};
+use crate::json::{Json};
+use crate::png;
+use crate::camera::{Camera};
+use crate::hittable::{Hittable, AlphaCutout};
+use crate::hittable_list::{HittableList};
+use crate::triangle::{Mesh, TriangleMesh};
+use crate::material::{Material, DiffuseLight};
+use crate::principled::{Principled, TexturedPrincipled};
+use crate::texture::{Texture, SolidColor, ImageTexture};
+use crate::bump::{Bumped, Perturbation};
+use crate::spectrum::{Emission};
+use crate::transform::{Mat4};
+
+/*
+    glTF 2.0 binary (.glb) scenes
+*/
+
+pub struct GltfScene {
+    pub world: HittableList,
+    // Perspective cameras in the order the scene's nodes are visited
+    pub cameras: Vec<Camera>,
+}
+
+fn decode_base64(s: &str) -> Result<Vec<u8>, String> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    };
+    let digits = s.bytes().filter(|&c| c != b'=' && !c.is_ascii_whitespace())
+        .map(|c| value(c).ok_or("bad base64 data"))
+        .collect::<Result<Vec<u8>, _>>()?;
+    Ok(digits.chunks(4).flat_map(|d| {
+        let n = d.iter().enumerate().fold(0u32, |n, (i, &v)| n | (v as u32) << (18 - 6 * i));
+        (0..d.len() - 1).map(move |k| (n >> (16 - 8 * k)) as u8)
+    }).collect())
+}
+
+// Decoded once and shared by every texture slot using it, so the values are kept as stored; the
+// sRGB base colour is decoded by TexturedPrincipled
+struct Image {
+    color: Rc<ImageTexture>,
+    width: usize,
+    height: usize,
+    alpha: Vec<f64>,
+}
+
+// A material, and the alpha to cut the surface with when it is not opaque
+struct MaterialInfo {
+    material: Rc<dyn Material>,
+    alpha: Option<Rc<dyn Texture>>,
+}
+
+// Accessor indices of one mesh primitive
+struct Primitive {
+    position: Option<usize>,
+    normal: Option<usize>,
+    texcoord: Option<usize>,
+    color: Option<usize>,
+    indices: Option<usize>,
+    material: Option<usize>,
+    mode: usize,
+}
+
+struct Loader<'a> {
+    doc: Json,
+    directory: &'a Path,
+    buffers: Vec<Vec<u8>>,
+    images: HashMap<usize, Rc<Image>>,
+    materials: HashMap<Option<usize>, Rc<MaterialInfo>>,
+    aspect_ratio: f64,
+}
+
+impl<'a> Loader<'a> {
+
+    fn buffer_view(&self, index: usize) -> Result<&[u8], String> {
+        let view = self.doc.get("bufferViews").elements().get(index).ok_or(format!("no buffer view {}", index))?;
+        let buffer = view.get("buffer").as_usize().and_then(|b| self.buffers.get(b)).ok_or(format!("buffer view {} has no buffer", index))?;
+        let offset = view.get("byteOffset").as_usize().unwrap_or(0);
+        let length = view.get("byteLength").as_usize().ok_or(format!("buffer view {} has no byteLength", index))?;
+        offset.checked_add(length).and_then(|end| buffer.get(offset..end)).ok_or(format!("buffer view {} is out of range", index))
+    }
+
+    // Reads an accessor as rows of components, scaling normalized integers to [0, 1] or [-1, 1]
+    fn accessor(&self, index: usize) -> Result<Vec<Vec<f64>>, String> {
+        let accessor = self.doc.get("accessors").elements().get(index).ok_or(format!("no accessor {}", index))?;
+        if !accessor.get("sparse").is_null() {
+            return Err(format!("accessor {} is sparse, which is not supported", index))
+        }
+        let count = accessor.get("count").as_usize().ok_or(format!("accessor {} has no count", index))?;
+        let components = match accessor.get("type").as_str() {
+            Some("SCALAR") => 1, Some("VEC2") => 2, Some("VEC3") => 3, Some("VEC4") => 4,
+            Some("MAT2") => 4, Some("MAT3") => 9, Some("MAT4") => 16,
+            _ => return Err(format!("accessor {} has a bad type", index)),
+        };
+        let component_type = accessor.get("componentType").as_usize().unwrap_or(0);
+        let (size, max) = match component_type {
+            5120 => (1, 127.0), 5121 => (1, 255.0), 5122 => (2, 32767.0), 5123 => (2, 65535.0),
+            5125 => (4, 4294967295.0), 5126 => (4, 1.0),
+            _ => return Err(format!("accessor {} has bad component type {}", index, component_type)),
+        };
+        let normalized = matches!(accessor.get("normalized"), Json::Bool(true));
+
+        let view_index = match accessor.get("bufferView").as_usize() {
+            Some(v) => v,
+            // No buffer view means all zeros. Such an accessor goes with stored ones of the same
+            // count, so more elements than the buffers have bytes can only be a bad count.
+            None => {
+                if count > self.buffers.iter().map(|b| b.len()).sum() {
+                    return Err(format!("accessor {} has a count of {} with no data", index, count))
+                }
+                return Ok(vec![vec![0.0; components]; count])
+            },
+        };
+        let data = self.buffer_view(view_index)?;
+        let stride = self.doc.get("bufferViews").elements()[view_index].get("byteStride").as_usize().unwrap_or(components * size);
+        let offset = accessor.get("byteOffset").as_usize().unwrap_or(0);
+        if count > 0 {
+            // The counts are untrusted, so the end of the last element must not overflow
+            let end = (count - 1).checked_mul(stride)
+                .and_then(|n| n.checked_add(offset))
+                .and_then(|n| n.checked_add(components * size));
+            if end.map_or(true, |end| end > data.len()) {
+                return Err(format!("accessor {} runs past its buffer view", index))
+            }
+        }
+
+        Ok((0..count).map(|i| (0..components).map(|c| {
+            let at = offset + i * stride + c * size;
+            let b = &data[at..at + size];
+            let value = match component_type {
+                5120 => b[0] as i8 as f64,
+                5121 => b[0] as f64,
+                5122 => i16::from_le_bytes([b[0], b[1]]) as f64,
+                5123 => u16::from_le_bytes([b[0], b[1]]) as f64,
+                5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            };
+            if normalized && component_type != 5126 {(value / max).max(-1.0)} else {value}
+        }).collect()).collect())
+    }
+
+    fn vectors(&self, index: usize) -> Result<Vec<Vec3>, String> {
+        Ok(self.accessor(index)?.iter().map(|v| Vec3(v[0], *v.get(1).unwrap_or(&0.0), *v.get(2).unwrap_or(&0.0))).collect())
+    }
+
+    // An image referenced by a texture, decoded once
+    fn image(&mut self, texture: usize) -> Result<Rc<Image>, String> {
+        let source = self.doc.get("textures").elements().get(texture).map(|t| t.get("source"))
+            .and_then(|s| s.as_usize()).ok_or(format!("texture {} has no image source", texture))?;
+        if let Some(image) = self.images.get(&source) {
+            return Ok(image.clone())
+        }
+        let image = self.doc.get("images").elements().get(source).ok_or(format!("no image {}", source))?;
+        let jpeg = image.get("mimeType").as_str() == Some("image/jpeg");
+        let data = match (image.get("bufferView").as_usize(), image.get("uri").as_str()) {
+            (Some(view), _) => self.buffer_view(view)?.to_vec(),
+            (None, Some(uri)) => self.uri(uri)?,
+            _ => return Err(format!("image {} has no data", source)),
+        };
+        if jpeg || data.starts_with(&[0xff, 0xd8]) {
+            return Err(format!("image {} is a JPEG, which is not supported; convert it to PNG", source))
+        }
+        let (width, height, colors, alpha) = png::decode(&data).map_err(|e| format!("image {}: {}", source, e))?;
+        let decoded = Rc::new(Image{color: Rc::new(ImageTexture::from_pixels(width, height, colors)), width, height, alpha});
+        self.images.insert(source, decoded.clone());
+        Ok(decoded)
+    }
+
+    // Data URIs, or files next to the .glb
+    fn uri(&self, uri: &str) -> Result<Vec<u8>, String> {
+        if let Some(data) = uri.strip_prefix("data:") {
+            let (_, encoded) = data.split_once(";base64,").ok_or("data URI is not base64")?;
+            return decode_base64(encoded)
+        }
+        let path = self.directory.join(uri);
+        fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // Maps a metallic-roughness material onto Principled. Emissive materials become lights, the
+    // transmission and ior extensions set transmission and ir, and only TEXCOORD_0 is used.
+    fn material(&mut self, index: Option<usize>) -> Result<Rc<MaterialInfo>, String> {
+        if let Some(m) = self.materials.get(&index) {
+            return Ok(m.clone())
+        }
+        let json = match index {
+            Some(i) => self.doc.get("materials").elements().get(i).ok_or(format!("no material {}", i))?,
+            None => &Json::Null,
+        };
+        let pbr = json.get("pbrMetallicRoughness");
+        let factor = pbr.get("baseColorFactor").as_numbers(4).unwrap_or_else(|| vec![1.0; 4]);
+        let mut principled = Principled::new(Vec3(factor[0], factor[1], factor[2]));
+        principled.metallic = pbr.get("metallicFactor").as_f64().unwrap_or(1.0);
+        principled.roughness = pbr.get("roughnessFactor").as_f64().unwrap_or(1.0);
+        let extensions = json.get("extensions");
+        principled.transmission = extensions.get("KHR_materials_transmission").get("transmissionFactor").as_f64().unwrap_or(0.0);
+        principled.ir = extensions.get("KHR_materials_ior").get("ior").as_f64().unwrap_or(1.5);
+        let emissive = json.get("emissiveFactor").as_numbers(3).map_or(Vec3(0.0, 0.0, 0.0), |e| Vec3(e[0], e[1], e[2]))
+            * extensions.get("KHR_materials_emissive_strength").get("emissiveStrength").as_f64().unwrap_or(1.0);
+
+        let base_texture = pbr.get("baseColorTexture").get("index").as_usize();
+        let metallic_roughness_texture = pbr.get("metallicRoughnessTexture").get("index").as_usize();
+        let normal_texture = json.get("normalTexture").get("index").as_usize();
+        let normal_scale = json.get("normalTexture").get("scale").as_f64().unwrap_or(1.0);
+        // MASK thresholds alpha at the cutoff; BLEND keeps fractional alpha as coverage
+        let cutoff = match json.get("alphaMode").as_str() {
+            None | Some("OPAQUE") => None,
+            Some("MASK") => Some(json.get("alphaCutoff").as_f64().unwrap_or(0.5)),
+            Some("BLEND") => Some(0.0),
+            Some(mode) => return Err(format!("unknown alphaMode '{}'", mode)),
+        };
+
+        let base_image = base_texture.map(|t| self.image(t)).transpose()?;
+        let metallic_roughness = metallic_roughness_texture.map(|t| self.image(t)).transpose()?
+            .map(|i| i.color.clone() as Rc<dyn Texture>);
+        let mut material: Rc<dyn Material> = if emissive.0.max(emissive.1).max(emissive.2) > 0.0 {
+            Rc::new(DiffuseLight::new(Emission::Rgb(emissive)))
+        } else {
+            Rc::new(TexturedPrincipled{
+                principled,
+                base_color: base_image.as_ref().map(|i| i.color.clone() as Rc<dyn Texture>),
+                metallic_roughness,
+            })
+        };
+        if let Some(t) = normal_texture {
+            material = Rc::new(Bumped{material, perturbation: Perturbation::NormalMap{map: self.image(t)?.color.clone(), strength: normal_scale}});
+        }
+
+        let alpha = cutoff.map(|cutoff| -> Rc<dyn Texture> {
+            let alpha = |a: f64| {
+                let a = a * factor[3];
+                let a = if cutoff > 0.0 {if a >= cutoff {1.0} else {0.0}} else {a};
+                Vec3(a, a, a)
+            };
+            match &base_image {
+                Some(i) => Rc::new(ImageTexture::from_pixels(i.width, i.height, i.alpha.iter().map(|&a| alpha(a)).collect())),
+                None => Rc::new(SolidColor{color: alpha(1.0)}),
+            }
+        });
+
+        let info = Rc::new(MaterialInfo{material, alpha});
+        self.materials.insert(index, info.clone());
+        Ok(info)
+    }
+
+    // Bakes the node transform into each triangle primitive of a mesh
+    fn mesh(&mut self, index: usize, transform: &Mat4, world: &mut HittableList) -> Result<(), String> {
+        let mesh = self.doc.get("meshes").elements().get(index).ok_or(format!("no mesh {}", index))?;
+        let primitives: Vec<Primitive> = mesh.get("primitives").elements().iter().map(|p| {
+            let attributes = p.get("attributes");
+            Primitive{
+                position: attributes.get("POSITION").as_usize(),
+                normal: attributes.get("NORMAL").as_usize(),
+                texcoord: attributes.get("TEXCOORD_0").as_usize(),
+                color: attributes.get("COLOR_0").as_usize(),
+                indices: p.get("indices").as_usize(),
+                material: p.get("material").as_usize(),
+                mode: p.get("mode").as_usize().unwrap_or(4),
+            }
+        }).collect();
+
+        let flip = transform.determinant() < 0.0;
+        for Primitive{position, normal, texcoord, color, indices, material, mode} in primitives {
+            // Points and lines have no surface
+            if !matches!(mode, 4..=6) {
+                continue
+            }
+            let positions: Vec<Point> = self.vectors(position.ok_or(format!("mesh {} has a primitive without positions", index))?)?
+                .into_iter().map(|p| transform.point(p)).collect();
+            let normals = match normal {
+                Some(n) => Some(self.vectors(n)?.into_iter().map(|n| transform.normal(n).unit_vector()).collect::<Vec<_>>()),
+                None => None,
+            };
+            // glTF puts the uv origin at the top left of the image
+            let uvs = match texcoord {
+                Some(t) => Some(self.accessor(t)?.iter().map(|uv| (uv[0], 1.0 - uv[1])).collect::<Vec<_>>()),
+                None => None,
+            };
+            let colors = match color {
+                Some(c) => Some(self.vectors(c)?),
+                None => None,
+            };
+            let order: Vec<usize> = match indices {
+                Some(i) => self.accessor(i)?.iter().map(|v| v[0] as usize).collect(),
+                None => (0..positions.len()).collect(),
+            };
+            if let Some(&i) = order.iter().find(|&&i| i >= positions.len()) {
+                return Err(format!("mesh {} has vertex index {} out of range ({} vertices)", index, i, positions.len()))
+            }
+
+            let mut triangles: Vec<[usize; 3]> = match mode {
+                4 => order.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect(),
+                // Strips alternate winding so that every triangle faces the same way
+                5 => (2..order.len()).map(|i| if i % 2 == 0 {[order[i - 2], order[i - 1], order[i]]} else {[order[i - 1], order[i - 2], order[i]]}).collect(),
+                _ => (2..order.len()).map(|i| [order[0], order[i - 1], order[i]]).collect(),
+            };
+            if flip {
+                for t in triangles.iter_mut() {
+                    t.swap(1, 2);
+                }
+            }
+
+            let info = self.material(material)?;
+            let mesh = TriangleMesh::new(Mesh{positions, normals, uvs, colors, triangles}, info.material.clone());
+            let object: Box<dyn Hittable> = match &info.alpha {
+                Some(alpha) => Box::new(AlphaCutout{object: Box::new(mesh), alpha: alpha.clone()}),
+                None => Box::new(mesh),
+            };
+            world.add(object);
+        }
+        Ok(())
+    }
+
+    // A perspective camera looking down the node's -z axis with +y up. The image's aspect ratio
+    // is used rather than the one stored with the camera.
+    fn camera(&self, index: usize, transform: &Mat4) -> Result<Option<Camera>, String> {
+        let camera = self.doc.get("cameras").elements().get(index).ok_or(format!("no camera {}", index))?;
+        if camera.get("type").as_str() != Some("perspective") {
+            return Ok(None)
+        }
+        let yfov = camera.get("perspective").get("yfov").as_f64().ok_or(format!("camera {} has no yfov", index))?;
+        let origin = transform.point(Vec3(0.0, 0.0, 0.0));
+        let forward = transform.vector(Vec3(0.0, 0.0, -1.0)).unit_vector();
+        let up = transform.vector(Vec3(0.0, 1.0, 0.0)).unit_vector();
+        Ok(Some(Camera::new(origin, origin + forward, up, yfov.to_degrees(), self.aspect_ratio, 0.0, 1.0)))
+    }
+
+    fn node(&mut self, index: usize, parent: &Mat4, depth: usize, scene: &mut GltfScene) -> Result<(), String> {
+        // The node graph must be a forest, but a malformed file could loop
+        if depth > 256 {
+            return Err("node hierarchy is too deep or cyclic".to_string())
+        }
+        let node = self.doc.get("nodes").elements().get(index).ok_or(format!("no node {}", index))?;
+        let local = match node.get("matrix").as_numbers(16) {
+            Some(m) => Mat4::from_columns(&m),
+            None => {
+                // Translation * rotation * scale
+                let t = node.get("translation").as_numbers(3).unwrap_or_else(|| vec![0.0; 3]);
+                let q = node.get("rotation").as_numbers(4).unwrap_or_else(|| vec![0.0, 0.0, 0.0, 1.0]);
+                let s = node.get("scale").as_numbers(3).unwrap_or_else(|| vec![1.0; 3]);
+                Mat4::translation(Vec3(t[0], t[1], t[2])) * Mat4::quaternion(q[0], q[1], q[2], q[3]) * Mat4::scaling(Vec3(s[0], s[1], s[2]))
+            },
+        };
+        let transform = *parent * local;
+        let mesh = node.get("mesh").as_usize();
+        let camera = node.get("camera").as_usize();
+        let children: Vec<usize> = node.get("children").elements().iter().filter_map(|c| c.as_usize()).collect();
+
+        if let Some(m) = mesh {
+            self.mesh(m, &transform, &mut scene.world)?;
+        }
+        if let Some(c) = camera {
+            if let Some(camera) = self.camera(c, &transform)? {
+                scene.cameras.push(camera);
+            }
+        }
+        for child in children {
+            self.node(child, &transform, depth + 1, scene)?;
+        }
+        Ok(())
+    }
+}
+
+// Splits a .glb container into its JSON and binary chunks
+fn chunks(data: &[u8]) -> Result<(&[u8], Option<&[u8]>), String> {
+    let word = |at: usize| data.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    if data.get(0..4) != Some(b"glTF".as_slice()) {
+        return Err("not a binary glTF file".to_string())
+    }
+    if word(4) != Some(2) {
+        return Err("only glTF version 2 is supported".to_string())
+    }
+    let mut pos = 12;
+    let mut json = None;
+    let mut bin = None;
+    while let (Some(length), Some(kind)) = (word(pos), word(pos + 4)) {
+        let body = data.get(pos + 8..pos + 8 + length).ok_or("truncated chunk")?;
+        match kind {
+            0x4e4f534a => json = Some(body),
+            0x004e4942 if bin.is_none() => bin = Some(body),
+            _ => {},
+        }
+        pos += 8 + length;
+    }
+    Ok((json.ok_or("missing JSON chunk")?, bin))
+}
+
+// Loads the default scene (or the first) of a .glb file. Meshes are baked into world space.
+pub fn load(path: &str, aspect_ratio: f64) -> io::Result<GltfScene> {
+    let data = fs::read(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, message));
+
+    let (json, bin) = chunks(&data).map_err(invalid)?;
+    let text = std::str::from_utf8(json).map_err(|_| invalid("JSON chunk is not UTF-8".to_string()))?;
+    let doc = Json::parse(text).map_err(invalid)?;
+    let mut loader = Loader{
+        doc,
+        directory: Path::new(path).parent().unwrap_or_else(|| Path::new("")),
+        buffers: Vec::new(),
+        images: HashMap::new(),
+        materials: HashMap::new(),
+        aspect_ratio,
+    };
+
+    // The first buffer without a uri is the binary chunk
+    for (i, buffer) in loader.doc.get("buffers").elements().iter().enumerate() {
+        let bytes = match buffer.get("uri").as_str() {
+            Some(uri) => loader.uri(uri).map_err(invalid)?,
+            None if i == 0 => bin.ok_or_else(|| invalid("buffer 0 needs the binary chunk".to_string()))?.to_vec(),
+            None => return Err(invalid(format!("buffer {} has no uri", i))),
+        };
+        loader.buffers.push(bytes);
+    }
+
+    let scene_index = loader.doc.get("scene").as_usize().unwrap_or(0);
+    let roots: Vec<usize> = match loader.doc.get("scenes").elements().get(scene_index) {
+        Some(scene) => scene.get("nodes").elements().iter().filter_map(|n| n.as_usize()).collect(),
+        None => Vec::new(),
+    };
+    let mut scene = GltfScene{world: HittableList::new(), cameras: Vec::new()};
+    for root in roots {
+        loader.node(root, &Mat4::IDENTITY, 0, &mut scene).map_err(invalid)?;
+    }
+    Ok(scene)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_with_and_without_padding() {
+        assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode_base64("aGVsbG8").unwrap(), b"hello");
+        assert_eq!(decode_base64("aGk=").unwrap(), b"hi");
+        assert_eq!(decode_base64("aGk").unwrap(), b"hi");
+        assert_eq!(decode_base64("aGV5").unwrap(), b"hey");
+        assert_eq!(decode_base64("").unwrap(), b"");
+    }
+
+    #[test]
+    fn base64_alphabets_and_whitespace() {
+        assert_eq!(decode_base64("+/8=").unwrap(), [0xfb, 0xff]);
+        assert_eq!(decode_base64("-_8=").unwrap(), [0xfb, 0xff]);
+        assert_eq!(decode_base64("aGVs\nbG8=\n").unwrap(), b"hello");
+    }
+
+    // A loader over one 16-byte buffer holding the floats 0, 1, 2 and 3
+    fn loader(doc: &str) -> Loader<'static> {
+        Loader{
+            doc: Json::parse(doc).unwrap(),
+            directory: Path::new(""),
+            buffers: vec![[0.0f32, 1.0, 2.0, 3.0].iter().flat_map(|f| f.to_le_bytes()).collect()],
+            images: HashMap::new(),
+            materials: HashMap::new(),
+            aspect_ratio: 1.0,
+        }
+    }
+
+    fn accessor(view: &str, accessor: &str) -> Result<Vec<Vec<f64>>, String> {
+        let doc = format!(r#"{{"bufferViews": [{}], "accessors": [{}]}}"#, view, accessor);
+        loader(&doc).accessor(0)
+    }
+
+    #[test]
+    fn accessor_reads_strided_elements() {
+        let rows = accessor(r#"{"buffer": 0, "byteLength": 16, "byteStride": 8}"#, r#"{"bufferView": 0, "byteOffset": 4, "count": 2, "type": "SCALAR", "componentType": 5126}"#).unwrap();
+        assert_eq!(rows, vec![vec![1.0], vec![3.0]]);
+        let zeros = accessor(r#"{"buffer": 0, "byteLength": 16}"#, r#"{"count": 3, "type": "VEC2", "componentType": 5126}"#).unwrap();
+        assert_eq!(zeros, vec![vec![0.0; 2]; 3]);
+    }
+
+    #[test]
+    fn huge_counts_and_offsets_are_rejected() {
+        let view = r#"{"buffer": 0, "byteLength": 16}"#;
+        let cases = [
+            (view, r#"{"bufferView": 0, "count": 3, "type": "VEC2", "componentType": 5126}"#, "runs past its buffer view"),
+            (view, r#"{"bufferView": 0, "count": 1e30, "type": "VEC4", "componentType": 5126}"#, "runs past its buffer view"),
+            (view, r#"{"bufferView": 0, "byteOffset": 1e30, "count": 1, "type": "SCALAR", "componentType": 5126}"#, "runs past its buffer view"),
+            (r#"{"buffer": 0, "byteLength": 16, "byteStride": 1e30}"#, r#"{"bufferView": 0, "count": 2, "type": "SCALAR", "componentType": 5126}"#, "runs past its buffer view"),
+            (r#"{"buffer": 0, "byteOffset": 1e30, "byteLength": 1e30}"#, r#"{"bufferView": 0, "count": 1, "type": "SCALAR", "componentType": 5126}"#, "buffer view 0 is out of range"),
+            (view, r#"{"count": 1e30, "type": "MAT4", "componentType": 5126}"#, "with no data"),
+        ];
+        for (view, accessor_json, message) in cases.iter() {
+            let err = accessor(view, accessor_json).expect_err(accessor_json);
+            assert!(err.contains(message), "{}: {}", accessor_json, err);
+        }
+    }
+
+    #[test]
+    fn base64_rejects_other_characters() {
+        assert_eq!(decode_base64("aGV*bG8=").unwrap_err(), "bad base64 data");
+    }
+}
@@ -1,32 +1,125 @@
 use std::rc::{Rc};
+use std::f64::consts::{PI};
 use crate::ray::{Ray};
-use crate::vec3::{Vec3,Point};
+use crate::aabb::{Aabb};
+use crate::vec3::{Vec3,Point,Color};
 use crate::material::{Material};
+use crate::onb::{Onb};
+use crate::texture::{Texture};
+use crate::color::{luminance};
+use rand::prelude::*;
 
+#[derive(Clone)]
 pub struct HitRecord {
     pub p: Point,
+    // Shading normal, which normal and bump maps may perturb
     pub normal: Vec3,
+    // True surface normal, on the same side as normal; decides front_face and ray offsets
+    pub geometric_normal: Vec3,
     pub material: Rc<dyn Material>,
     pub t: f64,
+    pub u: f64,
+    pub v: f64,
+    // Surface direction of increasing u, orthogonal to normal; orients anisotropic materials
+    pub tangent: Vec3,
+    // Interpolated colour of meshes with per-vertex colours
+    pub vertex_color: Option<Color>,
     pub front_face: bool
 }
 
 impl HitRecord {
-    fn new(p: Point, t: f64, r: &Ray, outward_normal: Vec3, material: Rc<dyn Material>) -> Self {
+    pub fn new(p: Point, t: f64, r: &Ray, outward_normal: Vec3, (u, v): (f64, f64), material: Rc<dyn Material>) -> Self {
         let front_face = r.direction.dot(outward_normal) < 0.0;
         let normal = if front_face {outward_normal} else {-outward_normal};
         Self{
-            p: p,
-            normal: normal,
-            material: material,
-            t: t,
-            front_face: front_face,
+            p,
+            normal,
+            geometric_normal: normal,
+            material,
+            t,
+            u,
+            v,
+            tangent: Onb::build_from_w(normal).u,
+            vertex_color: None,
+            front_face,
         }
     }
+
+    // Swaps in an interpolated shading normal, given on the outward side like outward_normal
+    pub fn with_shading_normal(self, outward_normal: Vec3) -> Self {
+        if outward_normal.is_near_zero() {
+            return self
+        }
+        let n = outward_normal.unit_vector();
+        let normal = if self.front_face {n} else {-n};
+        Self{normal, tangent: Onb::from_normal_tangent(normal, self.tangent).u, ..self}
+    }
+
+    pub fn with_tangent(self, tangent: Vec3) -> Self {
+        let t = tangent - tangent.dot(self.normal) * self.normal;
+        if t.is_near_zero() {
+            return self
+        }
+        Self{tangent: t.unit_vector(), ..self}
+    }
+}
+
+// Span of a ray inside a solid, between the hits where it enters and leaves
+pub struct Interval {
+    pub enter: HitRecord,
+    pub exit: HitRecord,
 }
 
+const MAX_CROSSINGS: usize = 256;
+
 pub trait Hittable {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    // None for objects without finite extent
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    // Every span of the whole line of the ray, behind its origin too, that lies inside the
+    // object, in order. Only closed objects have an inside. By default this steps through the
+    // surfaces with hit, counting entries and exits so overlapping parts merge into one span.
+    fn intervals(&self, r: &Ray) -> Vec<Interval> {
+        let mut intervals = Vec::new();
+        let mut depth = 0;
+        let mut enter = None;
+        let mut t_min = f64::NEG_INFINITY;
+        for _ in 0..MAX_CROSSINGS {
+            let rec = match self.hit(r, t_min, f64::INFINITY) {
+                Some(rec) => rec,
+                None => break,
+            };
+            t_min = rec.t + 1e-9 * rec.t.abs().max(1.0);
+            if rec.front_face {
+                depth += 1;
+                if depth == 1 {
+                    enter = Some(rec);
+                }
+            } else if depth > 0 {
+                depth -= 1;
+                if depth == 0 {
+                    intervals.push(Interval{enter: enter.take().unwrap(), exit: rec});
+                }
+            }
+        }
+        intervals
+    }
+}
+
+// Shared objects, such as geometry placed by several scene graph nodes
+impl<T: Hittable + ?Sized> Hittable for Rc<T> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        (**self).hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+
+    fn intervals(&self, r: &Ray) -> Vec<Interval> {
+        (**self).intervals(r)
+    }
 }
 
 pub struct Sphere {
@@ -35,29 +128,112 @@ pub struct Sphere {
     pub material: Rc<dyn Material>,
 }
 
-impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let oc = r.origin - self.center;
-        let a = r.direction.length_squared();
-        let half_b = oc.dot(r.direction);
-        let c = oc.length_squared() - self.radius*self.radius;
+impl Sphere {
+    // Maps a point on the unit sphere to (u, v) in [0, 1], with v going from -y to +y
+    fn get_uv(p: Point) -> (f64, f64) {
+        let theta = (-p.1).acos();
+        let phi = (-p.2).atan2(p.0) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+}
 
-        let discriminant = half_b*half_b - a*c;
-        if discriminant < 0.0 {
+// Sphere hit shared by the static and moving spheres
+fn hit_sphere(center: Point, radius: f64, material: &Rc<dyn Material>, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    let oc = r.origin - center;
+    let a = r.direction.length_squared();
+    let half_b = oc.dot(r.direction);
+    let c = oc.length_squared() - radius*radius;
+
+    let discriminant = half_b*half_b - a*c;
+    if discriminant < 0.0 {
+        return None
+    }
+    let sqrtd = discriminant.sqrt();
+
+    let mut root = (-half_b - sqrtd) / a;
+    if root < t_min || t_max < root {
+        root = (-half_b + sqrtd) / a;
+        if root < t_min || t_max < root {
             return None
         }
-        let sqrtd = discriminant.sqrt();
+    }
 
-        let mut root = (-half_b - sqrtd) / a;
-        if root < t_min || t_max < root {
-            root = (-half_b + sqrtd) / a;
-            if root < t_min || t_max < root {
-                return None
+    let p = r.at(root);
+    let outward_normal = (p - center) / radius;
+    let tangent = Vec3(outward_normal.2, 0.0, -outward_normal.0);
+    Some(HitRecord::new(p, root, r, outward_normal, Sphere::get_uv(outward_normal), material.clone()).with_tangent(tangent))
+}
+
+impl Hittable for Sphere {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        hit_sphere(self.center, self.radius, &self.material, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3(self.radius, self.radius, self.radius);
+        Some(Aabb{minimum: self.center - r, maximum: self.center + r})
+    }
+}
+
+// Sphere moving in a straight line from center0 at time0 to center1 at time1, resting at either
+// end outside that interval
+pub struct MovingSphere {
+    pub center0: Point,
+    pub center1: Point,
+    pub time0: f64,
+    pub time1: f64,
+    pub radius: f64,
+    pub material: Rc<dyn Material>,
+}
+
+impl MovingSphere {
+    fn center(&self, time: f64) -> Point {
+        let f = if self.time1 > self.time0 {((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0)} else {0.0};
+        self.center0 + f * (self.center1 - self.center0)
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        hit_sphere(self.center(r.time), self.radius, &self.material, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3(self.radius, self.radius, self.radius);
+        let b0 = Aabb{minimum: self.center0 - r, maximum: self.center0 + r};
+        let b1 = Aabb{minimum: self.center1 - r, maximum: self.center1 + r};
+        Some(Aabb::surrounding(&b0, &b1))
+    }
+}
+
+// Cuts holes into another object where the luminance of the alpha texture is below one.
+// Transparent texels are misses, so rays (including the ones that would be shadowed) continue to
+// whatever lies behind; fractional alpha is treated as the probability of a hit.
+pub struct AlphaCutout {
+    pub object: Box<dyn Hittable>,
+    pub alpha: Rc<dyn Texture>,
+}
+
+impl AlphaCutout {
+    const MAX_LAYERS: usize = 64;
+}
+
+impl Hittable for AlphaCutout {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut t_min = t_min;
+        for _ in 0..AlphaCutout::MAX_LAYERS {
+            let rec = self.object.hit(r, t_min, t_max)?;
+            let alpha = luminance(self.alpha.value(rec.u, rec.v, &rec.p));
+            if alpha >= 1.0 || (alpha > 0.0 && rand::thread_rng().gen_range(0.0..1.0) < alpha) {
+                return Some(rec)
             }
+            // Look for the next surface of the object behind the transparent one
+            t_min = rec.t + 1e-9 * rec.t.abs().max(1.0);
         }
+        None
+    }
 
-        let p = r.at(root);
-        let outward_normal = (p - self.center) / self.radius;
-        Some(HitRecord::new(p, root, r, outward_normal, self.material.clone()))
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
     }
-}
\ No newline at end of file
+}
@@ -1,5 +1,6 @@
 #![allow(unused)]
 use crate::ray::{Ray};
+use crate::aabb::{Aabb};
 use crate::hittable::{HitRecord, Hittable};
 
 pub struct HittableList {
@@ -14,11 +15,11 @@ impl HittableList {
         }
     }
 
-    pub fn clear(&mut self) -> () {
+    pub fn clear(&mut self) {
         self.objects.clear();
     }
 
-    pub fn add(&mut self, o: Box<dyn Hittable>) -> () {
+    pub fn add(&mut self, o: Box<dyn Hittable>) {
         self.objects.push(o);
     }
 }
@@ -28,13 +29,16 @@ impl Hittable for HittableList {
         let mut hit_found: Option<HitRecord> = None;
         let mut closest_so_far = t_max;
         for o in self.objects.iter() {
-            match o.hit(r, t_min, closest_so_far).map_or((hit_found, closest_so_far), |h| {let t = h.t; (Some(h), t)}) {
-                (hit, new_t) => {
-                    hit_found = hit;
-                    closest_so_far = new_t;
-                }
-            }
+            let (hit, new_t) = o.hit(r, t_min, closest_so_far).map_or((hit_found, closest_so_far), |h| {let t = h.t; (Some(h), t)});
+            hit_found = hit;
+            closest_so_far = new_t;
         }
         hit_found
     }
-}
\ No newline at end of file
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut objects = self.objects.iter();
+        let first = objects.next()?.bounding_box()?;
+        objects.try_fold(first, |b, o| Some(Aabb::surrounding(&b, &o.bounding_box()?)))
+    }
+}
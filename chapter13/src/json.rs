@@ -0,0 +1,299 @@
+/*
+    Minimal JSON reader, enough for glTF documents
+*/
+
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+static NULL: Json = Json::Null;
+
+// Deepest nesting of arrays and objects accepted, so hostile input cannot exhaust the stack
+const MAX_DEPTH: usize = 512;
+
+impl Json {
+
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser{bytes: text.as_bytes(), pos: 0};
+        let value = parser.value(0)?;
+        parser.whitespace();
+        if parser.pos != parser.bytes.len() {
+            return Err(parser.error("trailing characters"))
+        }
+        Ok(value)
+    }
+
+    // Member of an object, or Null when missing, so lookups can be chained
+    pub fn get(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map_or(&NULL, |(_, v)| v),
+            _ => &NULL,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Json::Null)
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    // Non-negative integers, as used for counts and indices
+    pub fn as_usize(&self) -> Option<usize> {
+        self.as_f64().filter(|n| *n >= 0.0 && n.fract() == 0.0).map(|n| n as usize)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    // Elements of an array; Null reads as empty, like a missing optional array
+    pub fn elements(&self) -> &[Json] {
+        match self {
+            Json::Array(a) => a,
+            _ => &[],
+        }
+    }
+
+    // Array of numbers of exactly the given length
+    pub fn as_numbers(&self, n: usize) -> Option<Vec<f64>> {
+        match self {
+            Json::Array(a) if a.len() == n => a.iter().map(|v| v.as_f64()).collect(),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+
+    fn error(&self, message: &str) -> String {
+        format!("JSON {} at byte {}", message, self.pos)
+    }
+
+    fn whitespace(&mut self) {
+        while self.pos < self.bytes.len() && matches!(self.bytes[self.pos], b' ' | b'\t' | b'\n' | b'\r') {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), String> {
+        if self.bytes[self.pos..].starts_with(literal.as_bytes()) {
+            self.pos += literal.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", literal)))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Json, String> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nesting too deep"))
+        }
+        self.whitespace();
+        match self.bytes.get(self.pos) {
+            Some(b'n') => self.expect("null").map(|_| Json::Null),
+            Some(b't') => self.expect("true").map(|_| Json::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut elements = Vec::new();
+                self.whitespace();
+                if self.bytes.get(self.pos) == Some(&b']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(elements))
+                }
+                loop {
+                    elements.push(self.value(depth + 1)?);
+                    self.whitespace();
+                    match self.bytes.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {self.pos += 1; return Ok(Json::Array(elements))},
+                        _ => return Err(self.error("expected ',' or ']'")),
+                    }
+                }
+            },
+            Some(b'{') => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                self.whitespace();
+                if self.bytes.get(self.pos) == Some(&b'}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(members))
+                }
+                loop {
+                    self.whitespace();
+                    if self.bytes.get(self.pos) != Some(&b'"') {
+                        return Err(self.error("expected a member name"))
+                    }
+                    let key = self.string()?;
+                    self.whitespace();
+                    self.expect(":")?;
+                    members.push((key, self.value(depth + 1)?));
+                    self.whitespace();
+                    match self.bytes.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => {self.pos += 1; return Ok(Json::Object(members))},
+                        _ => return Err(self.error("expected ',' or '}'")),
+                    }
+                }
+            },
+            Some(b'-') | Some(b'0'..=b'9') => {
+                let start = self.pos;
+                while self.pos < self.bytes.len() && matches!(self.bytes[self.pos], b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') {
+                    self.pos += 1;
+                }
+                std::str::from_utf8(&self.bytes[start..self.pos]).unwrap().parse().map(Json::Number)
+                    .map_err(|_| self.error("bad number"))
+            },
+            _ => Err(self.error("unexpected character")),
+        }
+    }
+
+    // A string starting at the opening quote, with escapes resolved
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            let start = self.pos;
+            while self.pos < self.bytes.len() && self.bytes[self.pos] != b'"' && self.bytes[self.pos] != b'\\' {
+                self.pos += 1;
+            }
+            s.push_str(std::str::from_utf8(&self.bytes[start..self.pos]).map_err(|_| self.error("invalid UTF-8"))?);
+            match self.bytes.get(self.pos) {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(s)
+                },
+                Some(b'\\') => {
+                    let c = match self.bytes.get(self.pos + 1) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            let code = self.hex4(self.pos + 2)?;
+                            self.pos += 4;
+                            // A surrogate pair is two escapes; anything else after a high surrogate is
+                            // left to be read on its own
+                            let low = if (0xd800..0xdc00).contains(&code) && self.bytes[self.pos + 2..].starts_with(b"\\u") {
+                                self.hex4(self.pos + 4).ok().filter(|low| (0xdc00..0xe000).contains(low))
+                            } else {
+                                None
+                            };
+                            match low {
+                                Some(low) => {
+                                    self.pos += 6;
+                                    char::from_u32(0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00)).unwrap_or('\u{fffd}')
+                                },
+                                None => char::from_u32(code).unwrap_or('\u{fffd}'),
+                            }
+                        },
+                        _ => return Err(self.error("bad escape")),
+                    };
+                    s.push(c);
+                    self.pos += 2;
+                },
+                _ => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn hex4(&self, at: usize) -> Result<u32, String> {
+        self.bytes.get(at..at + 4)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u32::from_str_radix(h, 16).ok())
+            .ok_or_else(|| self.error("bad unicode escape"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn documents_nest_and_chain() {
+        let doc = Json::parse(r#" {"a": [1, {"b": true}, null], "c": {}, "d": []} "#).unwrap();
+        assert_eq!(doc.get("a").elements().len(), 3);
+        assert!(matches!(doc.get("a").elements()[1].get("b"), Json::Bool(true)));
+        assert!(doc.get("a").elements()[2].is_null());
+        assert!(doc.get("missing").get("deeper").is_null());
+        assert!(doc.get("c").get("x").is_null() && doc.get("d").elements().is_empty());
+    }
+
+    #[test]
+    fn numbers() {
+        let doc = Json::parse("[0, -12, 3.25, -1.5e3, 2E-2, 1e+2]").unwrap();
+        let numbers: Vec<f64> = doc.elements().iter().map(|n| n.as_f64().unwrap()).collect();
+        assert_eq!(numbers, vec![0.0, -12.0, 3.25, -1500.0, 0.02, 100.0]);
+        assert_eq!(doc.elements()[5].as_usize(), Some(100));
+        assert_eq!(doc.elements()[1].as_usize(), None);
+        assert_eq!(doc.elements()[2].as_usize(), None);
+        assert_eq!(doc.as_numbers(6).map(|n| n.len()), Some(6));
+        assert!(doc.as_numbers(5).is_none());
+    }
+
+    #[test]
+    fn string_escapes() {
+        let doc = Json::parse(r#""q\" b\\ s\/ \b\f\n\r\t é 😀 end""#).unwrap();
+        assert_eq!(doc.as_str(), Some("q\" b\\ s/ \u{8}\u{c}\n\r\t \u{e9} \u{1f600} end"));
+        // A lone surrogate has no character
+        assert_eq!(Json::parse(r#""\ud83d""#).unwrap().as_str(), Some("\u{fffd}"));
+        // and the escape after it is not swallowed as its low half
+        assert_eq!(Json::parse(r#""\ud800\u0041""#).unwrap().as_str(), Some("\u{fffd}A"));
+        assert_eq!(Json::parse(r#""\ud800\ud83d\ude00""#).unwrap().as_str(), Some("\u{fffd}\u{1f600}"));
+        assert_eq!(Json::parse(r#""\udc00x""#).unwrap().as_str(), Some("\u{fffd}x"));
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let nested = |n: usize| format!("{}{}", "[".repeat(n), "]".repeat(n));
+        assert!(Json::parse(&nested(MAX_DEPTH)).is_ok());
+        let err = Json::parse(&nested(MAX_DEPTH + 2)).err().expect("should fail");
+        assert!(err.contains("nesting too deep"), "{}", err);
+        // Far deeper than the stack could recurse
+        assert!(Json::parse(&"[{\"a\":".repeat(500000)).is_err());
+    }
+
+    #[test]
+    fn malformed_documents_are_rejected() {
+        let cases = [
+            ("[1, 2", "expected ',' or ']' at byte 5"),
+            ("{\"a\" 1}", "expected ':'"),
+            ("{1: 2}", "expected a member name"),
+            ("\"open", "unterminated string"),
+            (r#""\x""#, "bad escape"),
+            (r#""\u12g4""#, "bad unicode escape"),
+            ("1e", "bad number"),
+            ("-", "bad number"),
+            ("tru", "expected 'true'"),
+            ("{} x", "trailing characters"),
+            ("", "unexpected character"),
+        ];
+        for (text, message) in cases.iter() {
+            let err = Json::parse(text).err().expect(text);
+            assert!(err.contains(message), "{}: {}", text, err);
+        }
+    }
+}
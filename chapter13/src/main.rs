@@ -1,21 +1,40 @@
 mod vec3;
 mod color;
 mod ray;
+mod aabb;
+mod bvh;
 mod hittable;
 mod hittable_list;
+mod triangle;
+mod planar;
+mod quadric;
+mod transform;
+mod scene_graph;
+mod csg;
+mod obj;
+mod ply;
+mod json;
+mod png;
+mod gltf;
 mod camera;
 mod material;
-
-use std::rc::{Rc};
+mod onb;
+mod microfacet;
+mod spectrum;
+mod principled;
+mod texture;
+mod perlin;
+mod bump;
+mod scenes;
 
 use rand::prelude::*;
 
 use crate::vec3::{Vec3, Color};
 use crate::ray::{Ray};
-use crate::hittable::{Hittable, Sphere};
-use crate::hittable_list::{HittableList};
-use crate::camera::{Camera};
-use crate::material::{Lambertian, Metal, Dielectric, Material};
+use crate::hittable::{Hittable};
+use crate::spectrum::{SampledWavelengths, SampledSpectrum};
+use crate::scenes::{Background, Scene};
+use crate::onb::{Onb};
 
 fn main() {
 
@@ -27,45 +46,15 @@ fn main() {
     let max_depth = 50;
 
     // World
-    let mut world = HittableList::new();
-
-    let ground_material = Rc::new(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)});
-    world.add(Box::new(Sphere{center:Vec3(0.0, -1000.0, 0.0), radius:1000.0, material: ground_material}));
-
-    let mut rng = rand::thread_rng();
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat = rng.gen_range(0.0..1.0);
-            let center = Vec3(a as f64 + 0.9+rng.gen_range(0.0..1.0), 0.2, b as f64 + 0.9*rng.gen_range(0.0..1.0));
-
-            if (center - Vec3(4.0, 0.2, 0.0)).length() > 0.9 {
-                let mat:Rc<dyn Material> = if choose_mat < 0.8 {
-                    Rc::new(Lambertian{albedo: Vec3::random() * Vec3::random()})
-                } else if choose_mat < 0.95 {
-                    Rc::new(Metal{albedo: Vec3::random_range(0.0..0.5), fuzz: rng.gen_range(0.0..0.5)})
-                } else {
-                    Rc::new(Dielectric{ir: 1.5})
-                };
-                world.add(Box::new(Sphere{center:center, radius:0.2, material: mat}));
-            }
-        }
-    }
-    
-    let material1 = Rc::new(Dielectric{ir:1.5});
-    world.add(Box::new(Sphere{center:Vec3(0.0, 1.0, 0.0), radius:1.0, material: material1}));
-
-    let material2 = Rc::new(Lambertian{albedo: Vec3(0.4, 0.2, 0.1)});
-    world.add(Box::new(Sphere{center:Vec3(-4.0, 1.0, 0.0), radius:1.0, material: material2}));
-
-    let material3 = Rc::new(Metal{albedo: Vec3(0.7, 0.6, 0.5), fuzz: 0.0});
-    world.add(Box::new(Sphere{center:Vec3(4.0, 1.0, 0.0), radius:1.0, material: material3}));
-
-    // Camera
-    let lookfrom = Vec3(13.0, 2.0, 3.0);
-    let lookat = Vec3(0.0, 0.0, 0.0);
-    let camera = Camera::new(lookfrom, lookat, Vec3(0.0, 1.0, 0.0), 20.0, aspect_ratio, 0.1, 10.0);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let spectral = args.iter().any(|a| a == "--spectral");
+    let scene_name = args.iter().find(|a| !a.starts_with("--")).cloned().unwrap_or_default();
+    // Directory for the shadow catcher mattes; asking for them writes them even without a catcher
+    let matte_dir = args.iter().find_map(|a| a.strip_prefix("--mattes="));
+    let scene = scenes::select(&scene_name, aspect_ratio);
 
     // Render
+    let mut mattes = Vec::new();
     println!("P3");
     println!("{} {}", image_width, image_height);
     println!("255");
@@ -74,19 +63,116 @@ fn main() {
         eprint!("\rScanlines remaining: {} ", y);
         for x in 0..image_width {
             let mut pixel_color = Vec3(0.0, 0.0, 0.0);
+            let mut matte = Matte::default();
             for _ in 0..samples_per_pixel {
                 let u = (x as f64 +  rand::thread_rng().gen_range(0.0..1.0)) / (image_width - 1) as f64;
                 let v = (y as f64 + rand::thread_rng().gen_range(0.0..1.0)) / (image_height - 1) as f64;
-                let r = camera.get_ray(u, v);
-                pixel_color += ray_color(&r, &world, max_depth);
+                let r = scene.camera.get_ray(u, v);
+                pixel_color += camera_sample(&r, &scene, max_depth, spectral, &mut matte);
             }
             color::write_color(&mut std::io::stdout(), &pixel_color, samples_per_pixel);
+            mattes.push(matte);
         }
     }
     eprintln!("\nDone.");
+
+    // Shadow catcher outputs, for compositing the render over a photograph
+    if matte_dir.is_some() || mattes.iter().any(|m| m.catcher > 0.0) {
+        let dir = std::path::Path::new(matte_dir.unwrap_or("."));
+        for (name, value) in [("shadow_matte.pgm", Matte::shadow as fn(&Matte) -> f64), ("alpha.pgm", Matte::alpha)].iter() {
+            let path = dir.join(name);
+            let mut file = std::io::BufWriter::new(std::fs::File::create(&path).expect("cannot create matte file"));
+            color::write_gray_header(&mut file, image_width, image_height);
+            for m in mattes.iter() {
+                color::write_gray(&mut file, value(m) / samples_per_pixel as f64);
+            }
+            eprintln!("Wrote {}", path.display());
+        }
+    }
+}
+
+// Per-pixel sums of camera samples that hit regular objects or a shadow catcher, and of the
+// light the catcher received compared to what it would receive with only light sources around
+#[derive(Default)]
+struct Matte {
+    coverage: f64,
+    catcher: f64,
+    received: f64,
+    unoccluded: f64,
+}
+
+impl Matte {
+    // Fraction of the catcher's light blocked by objects, summed over the catcher samples
+    fn shadow(&self) -> f64 {
+        if self.unoccluded <= 0.0 {
+            return 0.0
+        }
+        self.catcher * (1.0 - self.received / self.unoccluded).clamp(0.0, 1.0)
+    }
+
+    fn alpha(&self) -> f64 {
+        self.coverage + self.shadow()
+    }
+}
+
+fn radiance(r: &Ray, scene: &Scene, depth: i64, spectral: bool) -> Color {
+    if spectral {
+        let mut lambdas = SampledWavelengths::sample_hero();
+        let r = Ray{wavelength: Some(lambdas.hero()), ..*r};
+        ray_color_spectral(&r, &scene.world, &scene.background, depth, &mut lambdas).to_rgb(&lambdas)
+    } else {
+        ray_color(r, &scene.world, &scene.background, depth)
+    }
 }
 
-fn ray_color(r: &Ray, world: &dyn Hittable, depth: i64) -> Color {
+// Camera rays see through shadow catchers; a probe ray from the catcher measures its lighting
+fn camera_sample(r: &Ray, scene: &Scene, depth: i64, spectral: bool, matte: &mut Matte) -> Color {
+    match scene.world.hit(r, 0.0001, f64::INFINITY) {
+        Some(rec) if rec.material.is_shadow_catcher() => {
+            let d = Vec3::random_cosine_direction();
+            let probe = Ray{origin: rec.p, direction: Onb::build_from_w(rec.normal).local(d.0, d.1, d.2), ..*r};
+            matte.catcher += 1.0;
+            matte.received += color::luminance(radiance(&probe, scene, depth - 1, spectral));
+            matte.unoccluded += color::luminance(unoccluded_light(&probe, scene));
+
+            // Whatever lies behind the catcher, which may take several surfaces to get past
+            let mut behind = Ray{origin: rec.p, ..*r};
+            for _ in 0..16 {
+                match scene.world.hit(&behind, 0.0001, f64::INFINITY) {
+                    Some(next) if next.material.is_shadow_catcher() => behind.origin = next.p,
+                    _ => break,
+                }
+            }
+            radiance(&behind, scene, depth, spectral)
+        }
+        Some(_) => {
+            matte.coverage += 1.0;
+            radiance(r, scene, depth, spectral)
+        }
+        None => radiance(r, scene, depth, spectral),
+    }
+}
+
+// Light arriving along a ray if nothing but light sources were in the way: the first emitter
+// it meets, passing through everything else, or the background
+fn unoccluded_light(r: &Ray, scene: &Scene) -> Color {
+    let mut r = *r;
+    for _ in 0..64 {
+        match scene.world.hit(&r, 0.0001, f64::INFINITY) {
+            None => break,
+            Some(rec) => {
+                let emitted = rec.material.emitted(&rec);
+                if emitted.0 > 0.0 || emitted.1 > 0.0 || emitted.2 > 0.0 {
+                    return emitted
+                }
+                r.origin = rec.p;
+            }
+        }
+    }
+    scene.background.color(&r)
+}
+
+fn ray_color(r: &Ray, world: &dyn Hittable, background: &Background, depth: i64) -> Color {
 
     if depth <= 0 {
         return Vec3(0.0, 0.0, 0.0)
@@ -94,14 +180,34 @@ fn ray_color(r: &Ray, world: &dyn Hittable, depth: i64) -> Color {
 
     let hit = world.hit(r, 0.0001, f64::INFINITY);
     hit.map_or_else(
-        || {
-            let v = 0.5*(r.direction.unit_vector().1 + 1.0);
-            (1.0 - v) * Vec3(1.0, 1.0, 1.0) + v * Vec3(0.5, 0.7, 1.0)
-        },
-        |rec| {rec.material.scatter(r, &rec).map_or_else(
-            || {Vec3(0.0, 0.0, 0.0)},
-            |(scattered, attenuation)| {
-                attenuation * ray_color(&scattered, world, depth-1)
+        || {background.color(r)},
+        |rec| {
+            let emitted = rec.material.emitted(&rec);
+            rec.material.scatter(r, &rec).map_or_else(
+                || {emitted},
+                |(scattered, attenuation)| {
+                    emitted + attenuation * ray_color(&scattered, world, background, depth-1)
+                }
+            )
+        })
+}
+
+fn ray_color_spectral(r: &Ray, world: &dyn Hittable, background: &Background, depth: i64, lambdas: &mut SampledWavelengths) -> SampledSpectrum {
+
+    if depth <= 0 {
+        return SampledSpectrum::constant(0.0)
+    }
+
+    match world.hit(r, 0.0001, f64::INFINITY) {
+        None => SampledSpectrum::from_rgb(background.color(r), lambdas),
+        Some(rec) => {
+            let emitted = rec.material.emitted_spectral(&rec, lambdas);
+            match rec.material.scatter_spectral(r, &rec, lambdas) {
+                None => emitted,
+                Some((scattered, attenuation)) => {
+                    emitted + attenuation * ray_color_spectral(&scattered, world, background, depth-1, lambdas)
+                }
             }
-        )})
-}
\ No newline at end of file
+        }
+    }
+}
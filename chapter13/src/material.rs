@@ -1,52 +1,381 @@
 use std::cmp;
+use std::rc::{Rc};
 use rand::prelude::*;
 
 use crate::ray::{Ray};
 use crate::vec3::{Vec3, Color};
 use crate::hittable::{HitRecord};
+use crate::microfacet;
+use crate::spectrum;
+use crate::spectrum::{SampledWavelengths, SampledSpectrum, Emission};
+use crate::texture::{Texture};
+use crate::color::{luminance};
+use crate::onb::{Onb};
 
 pub trait Material {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)>;
+
+    fn emitted(&self, _rec: &HitRecord) -> Color {
+        Vec3(0.0, 0.0, 0.0)
+    }
+
+    // Spectral variants, by default the RGB results uplifted at the sampled wavelengths.
+    // Materials whose response depends on the wavelength override these.
+    fn scatter_spectral(&self, r_in: &Ray, rec: &HitRecord, lambdas: &mut SampledWavelengths) -> Option<(Ray, SampledSpectrum)> {
+        self.scatter(r_in, rec).map(|(scattered, attenuation)| (scattered, SampledSpectrum::from_rgb(attenuation, lambdas)))
+    }
+
+    fn emitted_spectral(&self, rec: &HitRecord, lambdas: &SampledWavelengths) -> SampledSpectrum {
+        SampledSpectrum::from_rgb(self.emitted(rec), lambdas)
+    }
+
+    // Camera rays pass through shadow catchers; see ShadowCatcher
+    fn is_shadow_catcher(&self) -> bool {
+        false
+    }
 }
 
+// Beer-Lambert attenuation for a ray that has just travelled through an absorbing medium.
+// A hit on the back face means r_in started inside the medium, at its previous interface.
+fn transmittance(absorption: Color, r_in: &Ray, rec: &HitRecord) -> Color {
+    if rec.front_face {
+        return Vec3(1.0, 1.0, 1.0)
+    }
+    let distance = rec.t * r_in.direction.length();
+    Vec3((-absorption.0 * distance).exp(), (-absorption.1 * distance).exp(), (-absorption.2 * distance).exp())
+}
+
+// On meshes with per-vertex colours the albedo is multiplied by the vertex colour
 pub struct Lambertian {
     pub albedo: Color
 }
 
 impl Material for Lambertian {
     
-    fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
         let mut scatter_direction = rec.normal + Vec3::random_unit_vector();
         if scatter_direction.is_near_zero() {
             scatter_direction = rec.normal;
         }
-        Some( (Ray{origin:rec.p, direction:scatter_direction}, self.albedo) )
+        let albedo = rec.vertex_color.map_or(self.albedo, |c| self.albedo * c);
+        Some( (Ray{origin:rec.p, direction:scatter_direction, ..*r_in}, albedo) )
+    }
+}
+
+// Rough diffuse reflection (Oren and Nayar 1994, qualitative model). sigma is the standard
+// deviation of the microfacet slope angle in radians; at zero this is exactly Lambertian.
+pub struct OrenNayar {
+    pub albedo: Color,
+    pub sigma: f64,
+}
+
+impl Material for OrenNayar {
+
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        let onb = Onb::build_from_w(rec.normal);
+        let wo = onb.to_local(-r_in.direction.unit_vector());
+        let wi = Vec3::random_cosine_direction();
+
+        let sigma2 = self.sigma * self.sigma;
+        let a = 1.0 - 0.5 * sigma2 / (sigma2 + 0.33);
+        let b = 0.45 * sigma2 / (sigma2 + 0.09);
+
+        let sin_o = (1.0 - wo.2 * wo.2).max(0.0).sqrt();
+        let sin_i = (1.0 - wi.2 * wi.2).max(0.0).sqrt();
+        let cos_phi_diff = if sin_o > 1e-4 && sin_i > 1e-4 {
+            ((wo.0 * wi.0 + wo.1 * wi.1) / (sin_o * sin_i)).max(0.0)
+        } else {
+            0.0
+        };
+        let (sin_alpha, tan_beta) = if wi.2 > wo.2 {
+            (sin_o, sin_i / wi.2)
+        } else {
+            (sin_i, sin_o / wo.2.max(1e-4))
+        };
+
+        // Cosine-weighted sampling cancels the Lambertian cos / pi term
+        let weight = a + b * cos_phi_diff * sin_alpha * tan_beta;
+        Some( (Ray{origin: rec.p, direction: onb.local(wi.0, wi.1, wi.2), ..*r_in}, weight * self.albedo) )
+    }
+}
+
+// A thin diffusing sheet such as paper, leaves or a lampshade: light is scattered diffusely
+// back on the side it came from or out through the other side, without refraction. It only
+// reflects from its front face, unless wrapped in TwoSided; transmission works the same in both
+// directions.
+pub struct ThinSheet {
+    pub reflectance: Color,
+    pub transmittance: Color,
+}
+
+impl Material for ThinSheet {
+
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        let reflectance = if rec.front_face {self.reflectance} else {Vec3(0.0, 0.0, 0.0)};
+        let r = luminance(reflectance);
+        let t = luminance(self.transmittance);
+        if r + t <= 0.0 {
+            return None
+        }
+
+        let transmit_probability = t / (r + t);
+        let (side, weight) = if rand::thread_rng().gen_range(0.0..1.0) < transmit_probability {
+            (-rec.normal, self.transmittance / transmit_probability)
+        } else {
+            (rec.normal, reflectance / (1.0 - transmit_probability))
+        };
+        let onb = Onb::build_from_w(side);
+        let d = Vec3::random_cosine_direction();
+        Some( (Ray{origin: rec.p, direction: onb.local(d.0, d.1, d.2), ..*r_in}, weight) )
+    }
+}
+
+// Makes a material treat both sides of an open surface, such as a Quad, Plane or Disk, as its front,
+// so one-sided behaviour like emission, the inside of a Dielectric or ThinSheet's back face does
+// not apply. The normal already faces the incoming ray, so only front_face changes.
+pub struct TwoSided {
+    pub material: Rc<dyn Material>,
+}
+
+impl TwoSided {
+    fn front(rec: &HitRecord) -> HitRecord {
+        HitRecord{front_face: true, ..rec.clone()}
+    }
+}
+
+impl Material for TwoSided {
+
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        self.material.scatter(r_in, &TwoSided::front(rec))
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.material.emitted(&TwoSided::front(rec))
+    }
+
+    fn scatter_spectral(&self, r_in: &Ray, rec: &HitRecord, lambdas: &mut SampledWavelengths) -> Option<(Ray, SampledSpectrum)> {
+        self.material.scatter_spectral(r_in, &TwoSided::front(rec), lambdas)
+    }
+
+    fn emitted_spectral(&self, rec: &HitRecord, lambdas: &SampledWavelengths) -> SampledSpectrum {
+        self.material.emitted_spectral(&TwoSided::front(rec), lambdas)
+    }
+
+    fn is_shadow_catcher(&self) -> bool {
+        self.material.is_shadow_catcher()
+    }
+}
+
+// Stand-in for the ground of a photograph that the render is composited over. It is invisible
+// to camera rays, but other objects see it as a diffuse surface, and the renderer writes out how
+// much of its light they block as a shadow matte and an alpha channel.
+pub struct ShadowCatcher {
+    pub albedo: Color,
+}
+
+impl Material for ShadowCatcher {
+
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        Lambertian{albedo: self.albedo}.scatter(r_in, rec)
+    }
+
+    fn is_shadow_catcher(&self) -> bool {
+        true
     }
 }
 
 pub struct Metal {
     pub albedo: Color,
     pub fuzz: f64,
+    pub film: Option<ThinFilm>,
+}
+
+impl Metal {
+    pub fn new(albedo: Color, fuzz: f64) -> Self {
+        Self{albedo, fuzz, film: None}
+    }
+
+    fn reflect(&self, r_in: &Ray, rec: &HitRecord) -> Option<Ray> {
+        let reflected = r_in.direction.unit_vector().reflect(rec.normal);
+        let scattered = Ray{origin: rec.p, direction: reflected + self.fuzz*Vec3::random_in_unit_sphere(), ..*r_in};
+        if scattered.direction.dot(rec.normal) > 0.0 {Some(scattered)} else {None}
+    }
 }
 
 impl Material for Metal {
 
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
-        let reflected = r_in.direction.unit_vector().reflect(rec.normal);
-        let scattered = Ray{origin: rec.p, direction: reflected + self.fuzz*Vec3::random_in_unit_sphere()};
-        if scattered.direction.dot(rec.normal) > 0.0 {
-            Some( (scattered, self.albedo) )
-        } else {
-            None
+        let scattered = self.reflect(r_in, rec)?;
+        let attenuation = match &self.film {
+            None => self.albedo,
+            Some(film) => {
+                let cos_i = (-r_in.direction.unit_vector()).dot(rec.normal);
+                let channels = [self.albedo.0, self.albedo.1, self.albedo.2];
+                let r = rgb_channels(r_in.wavelength, |i, lambda| film.reflectance_conductor(cos_i, channels[i], lambda));
+                Vec3(r[0], r[1], r[2])
+            }
+        };
+        Some( (scattered, attenuation) )
+    }
+
+    fn scatter_spectral(&self, r_in: &Ray, rec: &HitRecord, lambdas: &mut SampledWavelengths) -> Option<(Ray, SampledSpectrum)> {
+        let film = match &self.film {
+            None => return self.scatter(r_in, rec).map(|(scattered, attenuation)| (scattered, SampledSpectrum::from_rgb(attenuation, lambdas))),
+            Some(film) => film,
+        };
+        let scattered = self.reflect(r_in, rec)?;
+        let cos_i = (-r_in.direction.unit_vector()).dot(rec.normal);
+        let attenuation = SampledSpectrum::from_fn(lambdas, |l| {
+            film.reflectance_conductor(cos_i, spectrum::rgb_to_spectrum(self.albedo, l), l)
+        });
+        Some( (scattered, attenuation) )
+    }
+}
+
+// Microfacet conductor with separate GGX roughness along the surface tangent (u) and
+// bitangent (v) directions, e.g. brushed aluminium
+pub struct AnisotropicMetal {
+    pub albedo: Color,
+    pub roughness_u: f64,
+    pub roughness_v: f64,
+}
+
+impl Material for AnisotropicMetal {
+
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        let onb = Onb::from_normal_tangent(rec.normal, rec.tangent);
+        let wo = onb.to_local(-r_in.direction.unit_vector());
+        if wo.2 <= 0.0 {
+            return None
+        }
+        let alpha_x = microfacet::roughness_to_alpha(self.roughness_u);
+        let alpha_y = microfacet::roughness_to_alpha(self.roughness_v);
+
+        let h = microfacet::sample_ggx_vndf(wo, alpha_x, alpha_y);
+        let wi = 2.0 * wo.dot(h) * h - wo;
+        if wi.2 <= 0.0 {
+            return None
+        }
+
+        // Sampling visible normals leaves F G2 / G1(wo) as the weight
+        let fresnel = self.albedo + microfacet::schlick_weight(wi.dot(h)) * (Vec3(1.0, 1.0, 1.0) - self.albedo);
+        let g = microfacet::ggx_g2_aniso(wo, wi, alpha_x, alpha_y) / microfacet::ggx_g1_aniso(wo, alpha_x, alpha_y);
+        Some( (Ray{origin: rec.p, direction: onb.local(wi.0, wi.1, wi.2), ..*r_in}, g * fresnel) )
+    }
+}
+
+// Wavelength-dependent index of refraction; the coefficients take wavelengths in micrometres
+#[derive(Clone, Copy)]
+pub enum Dispersion {
+    // n = a + b / lambda^2
+    Cauchy{a: f64, b: f64},
+    // n^2 = 1 + sum(b_i lambda^2 / (lambda^2 - c_i))
+    Sellmeier{b: [f64; 3], c: [f64; 3]},
+}
+
+impl Dispersion {
+    pub fn ior(&self, lambda_nm: f64) -> f64 {
+        let l2 = (lambda_nm / 1000.0).powi(2);
+        match self {
+            Dispersion::Cauchy{a, b} => a + b / l2,
+            Dispersion::Sellmeier{b, c} => {
+                (1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f64>()).sqrt()
+            }
         }
     }
 }
 
+// A thin transparent layer on top of a surface (soap film, oil slick, anti-reflective coating).
+// Light reflected from its top and bottom interfaces interferes, so the reflectance depends on
+// the wavelength and the viewing angle (Airy summation, ignoring polarization mixing).
+#[derive(Clone, Copy)]
+pub struct ThinFilm {
+    // In nanometres
+    pub thickness: f64,
+    pub ir: f64,
+}
+
+fn fresnel_amplitudes(n_i: f64, n_t: f64, cos_i: f64, cos_t: f64) -> (f64, f64) {
+    let rs = (n_i * cos_i - n_t * cos_t) / (n_i * cos_i + n_t * cos_t);
+    let rp = (n_t * cos_i - n_i * cos_t) / (n_t * cos_i + n_i * cos_t);
+    (rs, rp)
+}
+
+fn airy_reflectance(r12: f64, r23: f64, phase: f64) -> f64 {
+    let c = 2.0 * r12 * r23 * phase.cos();
+    ((r12 * r12 + r23 * r23 + c) / (1.0 + r12 * r12 * r23 * r23 + c)).clamp(0.0, 1.0)
+}
+
+impl ThinFilm {
+
+    // Cosine of the angle inside the film, or None under total internal reflection
+    fn cos_in_film(&self, cos_i: f64, n_i: f64) -> Option<f64> {
+        let sin2 = (n_i / self.ir).powi(2) * (1.0 - cos_i * cos_i);
+        if sin2 >= 1.0 {None} else {Some((1.0 - sin2).sqrt())}
+    }
+
+    fn phase(&self, cos_film: f64, lambda: f64) -> f64 {
+        4.0 * std::f64::consts::PI * self.ir * self.thickness * cos_film / lambda
+    }
+
+    // Reflectance of the film over a dielectric substrate of index n_t, seen from a medium of index n_i
+    pub fn reflectance_dielectric(&self, cos_i: f64, n_i: f64, n_t: f64, lambda: f64) -> f64 {
+        let cos_i = cos_i.clamp(0.0, 1.0);
+        let sin2_t = (n_i / n_t).powi(2) * (1.0 - cos_i * cos_i);
+        let cos_film = match self.cos_in_film(cos_i, n_i) {
+            Some(c) if sin2_t < 1.0 => c,
+            _ => return 1.0,
+        };
+        let cos_t = (1.0 - sin2_t).sqrt();
+        let (rs12, rp12) = fresnel_amplitudes(n_i, self.ir, cos_i, cos_film);
+        let (rs23, rp23) = fresnel_amplitudes(self.ir, n_t, cos_film, cos_t);
+        let phase = self.phase(cos_film, lambda);
+        0.5 * (airy_reflectance(rs12, rs23, phase) + airy_reflectance(rp12, rp23, phase))
+    }
+
+    // Reflectance of the film over a metal whose bare reflectance is substrate. The metal is
+    // approximated as a perfect mirror with a half-wave phase shift, scaled to that reflectance.
+    pub fn reflectance_conductor(&self, cos_i: f64, substrate: f64, lambda: f64) -> f64 {
+        let cos_i = cos_i.clamp(0.0, 1.0);
+        let cos_film = match self.cos_in_film(cos_i, 1.0) {
+            Some(c) => c,
+            None => return 1.0,
+        };
+        let r23 = -substrate.clamp(0.0, 1.0).sqrt();
+        let (rs12, rp12) = fresnel_amplitudes(1.0, self.ir, cos_i, cos_film);
+        let phase = self.phase(cos_film, lambda);
+        0.5 * (airy_reflectance(rs12, r23, phase) + airy_reflectance(rp12, r23, phase))
+    }
+}
+
+// Evaluates a per-wavelength quantity for the three RGB channels, either at representative
+// wavelengths or, once the path has collapsed to one wavelength, at that wavelength only.
+fn rgb_channels(wavelength: Option<f64>, f: impl Fn(usize, f64) -> f64) -> [f64; 3] {
+    let mut values = [0.0; 3];
+    for (i, v) in values.iter_mut().enumerate() {
+        *v = f(i, wavelength.unwrap_or(spectrum::RGB_WAVELENGTHS[i]));
+    }
+    values
+}
+
 pub struct Dielectric {
     pub ir: f64,
+    // Absorption coefficient per unit distance travelled inside the medium (Beer-Lambert)
+    pub absorption: Color,
+    // When set, ir is ignored and the path is traced at a single sampled wavelength
+    pub dispersion: Option<Dispersion>,
+    pub film: Option<ThinFilm>,
 }
 
 impl Dielectric {
+    pub fn new(ir: f64) -> Self {
+        Self{ir, absorption: Vec3(0.0, 0.0, 0.0), dispersion: None, film: None}
+    }
+
+    pub fn dispersive(dispersion: Dispersion) -> Self {
+        Self{dispersion: Some(dispersion), ..Self::new(dispersion.ior(550.0))}
+    }
+
     fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
         let mut r0 = (1.0-ref_idx) / (1.0+ref_idx);
         r0 = r0 * r0;
@@ -54,21 +383,318 @@ impl Dielectric {
     }
 }
 
+impl Dielectric {
+
+    // Indices of refraction on the incident and transmitted sides
+    fn indices(rec: &HitRecord, ir: f64) -> (f64, f64) {
+        if rec.front_face {(1.0, ir)} else {(ir, 1.0)}
+    }
+
+    // Reflects with probability equal to the mean of the per-channel reflectances, or refracts,
+    // returning the new direction and the per-channel weights of that choice
+    fn choose<const N: usize>(r_in: &Ray, rec: &HitRecord, ir: f64, reflectance: [f64; N]) -> (Vec3, [f64; N]) {
+        let (n_i, n_t) = Dielectric::indices(rec, ir);
+        let unit_direction = r_in.direction.unit_vector();
+        let p = reflectance.iter().sum::<f64>() / N as f64;
+        let mut weights = reflectance;
+        if p > rand::thread_rng().gen_range(0.0..1.0) {
+            for w in weights.iter_mut() {
+                *w /= p;
+            }
+            (unit_direction.reflect(rec.normal), weights)
+        } else {
+            for w in weights.iter_mut() {
+                *w = (1.0 - *w) / (1.0 - p);
+            }
+            (unit_direction.refract(rec.normal, n_i / n_t), weights)
+        }
+    }
+
+    fn cos_theta(r_in: &Ray, rec: &HitRecord) -> f64 {
+        cmp::min_by((-r_in.direction.unit_vector()).dot(rec.normal), 1.0, |x, y| {x.partial_cmp(y).unwrap()})
+    }
+
+    fn interface_reflectance(&self, r_in: &Ray, rec: &HitRecord, ir: f64, lambda: f64) -> f64 {
+        let (n_i, n_t) = Dielectric::indices(rec, ir);
+        let cos_thetha = Dielectric::cos_theta(r_in, rec);
+        match &self.film {
+            Some(film) => film.reflectance_dielectric(cos_thetha, n_i, n_t, lambda),
+            None => {
+                let refraction_ratio = n_i / n_t;
+                let sin_thetha = (1.0 - cos_thetha * cos_thetha).sqrt();
+                let cannot_refract = refraction_ratio * sin_thetha > 1.0;
+                if cannot_refract {1.0} else {Dielectric::reflectance(cos_thetha, refraction_ratio)}
+            }
+        }
+    }
+}
+
 impl Material for Dielectric {
 
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
-        let refraction_ratio = if rec.front_face {1.0/self.ir} else {self.ir};
-        let unit_direction = r_in.direction.unit_vector();
-        let cos_thetha = cmp::min_by((-unit_direction).dot(rec.normal), 1.0, |x, y| {x.partial_cmp(y).unwrap()});
-        let sin_thetha = (1.0 - cos_thetha * cos_thetha).sqrt();
-        let cannot_refract = refraction_ratio * sin_thetha > 1.0;
+        // A dispersive interface collapses an RGB path to one wavelength, weighted by its colour
+        let (ir, wavelength, weight) = match (self.dispersion, r_in.wavelength) {
+            (Some(d), Some(lambda)) => (d.ior(lambda), Some(lambda), Vec3(1.0, 1.0, 1.0)),
+            (Some(d), None) => {
+                let lambda = spectrum::sample_wavelength();
+                (d.ior(lambda), Some(lambda), spectrum::wavelength_to_rgb(lambda))
+            }
+            (None, wavelength) => (self.ir, wavelength, Vec3(1.0, 1.0, 1.0)),
+        };
 
-        let direction = if cannot_refract || (Dielectric::reflectance(cos_thetha, refraction_ratio) > rand::thread_rng().gen_range(0.0..1.0)) {
-            unit_direction.reflect(rec.normal)
-        } else {
-            unit_direction.refract(rec.normal, refraction_ratio)
+        let reflectance = rgb_channels(wavelength, |_, lambda| self.interface_reflectance(r_in, rec, ir, lambda));
+        let (direction, w) = Dielectric::choose(r_in, rec, ir, reflectance);
+
+        Some( (Ray{origin:rec.p, direction, wavelength, ..*r_in}, weight * Vec3(w[0], w[1], w[2]) * transmittance(self.absorption, r_in, rec)) )
+    }
+
+    fn scatter_spectral(&self, r_in: &Ray, rec: &HitRecord, lambdas: &mut SampledWavelengths) -> Option<(Ray, SampledSpectrum)> {
+        // Spectral rays always carry the hero wavelength, so only the companions need dropping
+        let ir = match self.dispersion {
+            Some(d) => {
+                lambdas.terminate_secondary();
+                d.ior(lambdas.hero())
+            }
+            None => self.ir,
+        };
+
+        let mut reflectance = [0.0; spectrum::N_SAMPLES];
+        for (r, lambda) in reflectance.iter_mut().zip(lambdas.lambda.iter()) {
+            *r = self.interface_reflectance(r_in, rec, ir, *lambda);
+        }
+        let (direction, w) = Dielectric::choose(r_in, rec, ir, reflectance);
+
+        let absorption = SampledSpectrum::from_rgb(transmittance(self.absorption, r_in, rec), lambdas);
+        Some( (Ray{origin:rec.p, direction, ..*r_in}, SampledSpectrum(w) * absorption) )
+    }
+}
+
+pub struct RoughDielectric {
+    pub ir: f64,
+    pub roughness: f64,
+    pub absorption: Color,
+}
+
+impl RoughDielectric {
+    pub fn new(ir: f64, roughness: f64) -> Self {
+        Self{ir, roughness, absorption: Vec3(0.0, 0.0, 0.0)}
+    }
+}
+
+// Samples reflection or refraction through a rough dielectric interface (Walter et al. 2007),
+// returning the new direction and its weight.
+pub fn sample_rough_dielectric(r_in: &Ray, rec: &HitRecord, ir: f64, roughness: f64) -> Option<(Vec3, f64)> {
+    let (eta_i, eta_t) = if rec.front_face {(1.0, ir)} else {(ir, 1.0)};
+    let alpha = microfacet::roughness_to_alpha(roughness);
+    let n = rec.normal;
+    let i = -r_in.direction.unit_vector();
+    let m = microfacet::sample_ggx(n, alpha);
+
+    let cos_im = i.dot(m);
+    if cos_im <= 0.0 {
+        return None
+    }
+
+    let fresnel = microfacet::fresnel_dielectric(cos_im, eta_i, eta_t);
+    let direction = if fresnel > rand::thread_rng().gen_range(0.0..1.0) {
+        let o = 2.0 * cos_im * m - i;
+        if o.dot(n) <= 0.0 {
+            return None
+        }
+        o
+    } else {
+        let eta = eta_i / eta_t;
+        let o = (eta * cos_im - (1.0 + eta * eta * (cos_im * cos_im - 1.0)).sqrt()) * m - eta * i;
+        if o.dot(n) >= 0.0 {
+            return None
+        }
+        o
+    };
+
+    // Walter et al. eq. 41 with the Fresnel term cancelled by the reflect/refract choice.
+    let g = microfacet::ggx_g1(i, m, n, alpha) * microfacet::ggx_g1(direction, m, n, alpha);
+    Some( (direction, cos_im * g / (i.dot(n) * m.dot(n))) )
+}
+
+impl Material for RoughDielectric {
+
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        let (direction, weight) = sample_rough_dielectric(r_in, rec, self.ir, self.roughness)?;
+        Some( (Ray{origin: rec.p, direction, ..*r_in}, weight * transmittance(self.absorption, r_in, rec)) )
+    }
+}
+
+// Subsurface scattering by a volumetric random walk inside a closed object (wax, marble, jade,
+// skin). Rays enter and leave through a smooth dielectric boundary; inside, the distance to the
+// next scattering event is sampled per channel from the mean free path, so each hit on the
+// inner side of the surface is either reached or replaced by a scattering event in between.
+// Every event is one bounce of the path, so a short mean free path needs a large max depth.
+pub struct Subsurface {
+    pub boundary: Dielectric,
+    // Probability of scattering rather than absorbing at each event
+    pub albedo: Color,
+    // Average distance between events, per channel
+    pub mean_free_path: Color,
+}
+
+impl Subsurface {
+    pub fn new(ir: f64, albedo: Color, mean_free_path: Color) -> Self {
+        Self{boundary: Dielectric::new(ir), albedo, mean_free_path}
+    }
+}
+
+impl Material for Subsurface {
+
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        if rec.front_face {
+            return self.boundary.scatter(r_in, rec)
+        }
+
+        let sigma_t = [self.mean_free_path.0, self.mean_free_path.1, self.mean_free_path.2].map(|d| 1.0 / d.max(1e-6));
+        let transmittance = |d: f64| sigma_t.map(|s| (-s * d).exp());
+        let mean = |v: [f64; 3]| v.iter().sum::<f64>() / 3.0;
+
+        // Free-flight distance sampled from one channel; the weights use the pdf averaged over channels
+        let mut rng = rand::thread_rng();
+        let channel = rng.gen_range(0..3);
+        let distance = -(1.0 - rng.gen_range(0.0..1.0f64)).ln() / sigma_t[channel];
+        let boundary_distance = rec.t * r_in.direction.length();
+
+        if distance < boundary_distance {
+            let t = transmittance(distance);
+            let pdf = mean([sigma_t[0] * t[0], sigma_t[1] * t[1], sigma_t[2] * t[2]]);
+            let weight = Vec3(t[0] * sigma_t[0], t[1] * sigma_t[1], t[2] * sigma_t[2]) * self.albedo / pdf;
+            let origin = r_in.origin + distance * r_in.direction.unit_vector();
+            return Some( (Ray{origin, direction: Vec3::random_unit_vector(), ..*r_in}, weight) )
+        }
+
+        let t = transmittance(boundary_distance);
+        let weight = Vec3(t[0], t[1], t[2]) / mean(t);
+        self.boundary.scatter(r_in, rec).map(|(scattered, attenuation)| (scattered, weight * attenuation))
+    }
+}
+
+pub struct DiffuseLight {
+    pub emission: Emission,
+    rgb: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emission: Emission) -> Self {
+        Self{emission, rgb: emission.rgb()}
+    }
+}
+
+impl Material for DiffuseLight {
+
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<(Ray, Color)> {
+        None
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        if rec.front_face {self.rgb} else {Vec3(0.0, 0.0, 0.0)}
+    }
+
+    fn emitted_spectral(&self, rec: &HitRecord, lambdas: &SampledWavelengths) -> SampledSpectrum {
+        if rec.front_face {self.emission.sample(lambdas)} else {SampledSpectrum::constant(0.0)}
+    }
+}
+
+// A dielectric coating over any base material (clearcoat, varnish). Light refracts into the
+// coat, scatters off the base and may bounce between the base and the underside of the coat
+// several times before leaving, all at the same hit point.
+pub struct Coated {
+    pub base: Rc<dyn Material>,
+    pub ir: f64,
+    pub roughness: f64,
+    // Colour of the coat after one perpendicular pass through it
+    pub tint: Color,
+}
+
+impl Coated {
+    const MAX_INTERNAL_BOUNCES: usize = 16;
+
+    fn coat_transmittance(&self, direction: Vec3, normal: Vec3) -> Color {
+        let inv_cos = 1.0 / direction.unit_vector().dot(normal).abs().max(1e-4);
+        Vec3(self.tint.0.powf(inv_cos), self.tint.1.powf(inv_cos), self.tint.2.powf(inv_cos))
+    }
+}
+
+impl Material for Coated {
+
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        let outside = HitRecord{front_face: true, ..rec.clone()};
+        let inside = HitRecord{normal: -rec.normal, front_face: false, ..rec.clone()};
+
+        let (mut direction, w) = sample_rough_dielectric(r_in, &outside, self.ir, self.roughness)?;
+        let mut weight = Vec3(w, w, w);
+
+        for _ in 0..Coated::MAX_INTERNAL_BOUNCES {
+            if direction.dot(rec.normal) > 0.0 {
+                return Some( (Ray{origin: rec.p, direction, ..*r_in}, weight) )
+            }
+
+            // Down through the coat to the base, which is opaque
+            weight = weight * self.coat_transmittance(direction, rec.normal);
+            let (scattered, attenuation) = self.base.scatter(&Ray{direction, ..*r_in}, &outside)?;
+            if scattered.direction.dot(rec.normal) <= 0.0 {
+                return None
+            }
+            weight = weight * attenuation * self.coat_transmittance(scattered.direction, rec.normal);
+
+            // Back up to the coat, where it either leaves or is reflected down again
+            let (next, w) = sample_rough_dielectric(&scattered, &inside, self.ir, self.roughness)?;
+            direction = next;
+            weight = w * weight;
+        }
+        None
+    }
+}
+
+pub enum MixWeight {
+    Constant(f64),
+    // Luminance of the texture at the hit point
+    Mask(Rc<dyn Texture>),
+}
+
+// Picks material b with probability weight, otherwise material a
+pub struct MixMaterial {
+    pub a: Rc<dyn Material>,
+    pub b: Rc<dyn Material>,
+    pub weight: MixWeight,
+}
+
+impl MixMaterial {
+    fn weight(&self, rec: &HitRecord) -> f64 {
+        let w = match &self.weight {
+            MixWeight::Constant(w) => *w,
+            MixWeight::Mask(texture) => luminance(texture.value(rec.u, rec.v, &rec.p)),
         };
+        w.clamp(0.0, 1.0)
+    }
+
+    fn choose(&self, rec: &HitRecord) -> &Rc<dyn Material> {
+        if rand::thread_rng().gen_range(0.0..1.0) < self.weight(rec) {&self.b} else {&self.a}
+    }
+}
+
+impl Material for MixMaterial {
+
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        self.choose(rec).scatter(r_in, rec)
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        let w = self.weight(rec);
+        (1.0 - w) * self.a.emitted(rec) + w * self.b.emitted(rec)
+    }
 
-        Some( (Ray{origin:rec.p, direction:direction}, Vec3(1.0, 1.0, 1.0)) )
+    fn scatter_spectral(&self, r_in: &Ray, rec: &HitRecord, lambdas: &mut SampledWavelengths) -> Option<(Ray, SampledSpectrum)> {
+        self.choose(rec).scatter_spectral(r_in, rec, lambdas)
     }
-}
\ No newline at end of file
+
+    fn emitted_spectral(&self, rec: &HitRecord, lambdas: &SampledWavelengths) -> SampledSpectrum {
+        let w = self.weight(rec);
+        SampledSpectrum::constant(1.0 - w) * self.a.emitted_spectral(rec, lambdas) + SampledSpectrum::constant(w) * self.b.emitted_spectral(rec, lambdas)
+    }
+}
@@ -0,0 +1,141 @@
+use std::f64::consts::{PI};
+use rand::prelude::*;
+
+use crate::vec3::{Vec3};
+use crate::onb::{Onb};
+
+/*
+    GGX (Trowbridge-Reitz) microfacet distribution and Fresnel terms
+    (Walter et al. 2007, "Microfacet Models for Refraction through Rough Surfaces")
+*/
+
+// Converts a perceptual roughness in [0, 1] to the GGX alpha parameter.
+pub fn roughness_to_alpha(roughness: f64) -> f64 {
+    (roughness * roughness).max(1e-3)
+}
+
+// Smith shadowing term for one direction v against microfacet normal m.
+pub fn ggx_g1(v: Vec3, m: Vec3, n: Vec3, alpha: f64) -> f64 {
+    let cos_v = v.dot(n);
+    if v.dot(m) * cos_v <= 0.0 {
+        return 0.0
+    }
+    let cos2 = cos_v * cos_v;
+    let tan2 = (1.0 - cos2) / cos2;
+    2.0 / (1.0 + (1.0 + alpha * alpha * tan2).sqrt())
+}
+
+// Samples a microfacet normal around n proportionally to D(m) |m.n|.
+pub fn sample_ggx(n: Vec3, alpha: f64) -> Vec3 {
+    let mut rng = rand::thread_rng();
+    let r1: f64 = rng.gen_range(0.0..1.0);
+    let r2: f64 = rng.gen_range(0.0..1.0);
+    let theta = (alpha * (r1 / (1.0 - r1)).sqrt()).atan();
+    let phi = 2.0 * PI * r2;
+    let onb = Onb::build_from_w(n);
+    onb.local(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos())
+}
+
+// Unpolarized Fresnel reflectance of a dielectric interface.
+// cos_theta_i is measured on the incident side, eta_i / eta_t are the indices on each side.
+pub fn fresnel_dielectric(cos_theta_i: f64, eta_i: f64, eta_t: f64) -> f64 {
+    let cos_i = cos_theta_i.clamp(0.0, 1.0);
+    let sin2_t = (eta_i / eta_t).powi(2) * (1.0 - cos_i * cos_i);
+    if sin2_t >= 1.0 {
+        return 1.0
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let rs = (eta_i * cos_i - eta_t * cos_t) / (eta_i * cos_i + eta_t * cos_t);
+    let rp = (eta_t * cos_i - eta_i * cos_t) / (eta_t * cos_i + eta_i * cos_t);
+    0.5 * (rs * rs + rp * rp)
+}
+
+/*
+    Anisotropic GGX in a local frame where the normal is +z and the tangent is +x
+    (Heitz 2014, "Understanding the Masking-Shadowing Function"; Heitz 2018, "Sampling the GGX
+    Distribution of Visible Normals")
+*/
+
+pub fn ggx_d_aniso(h: Vec3, alpha_x: f64, alpha_y: f64) -> f64 {
+    if h.2 <= 0.0 {
+        return 0.0
+    }
+    let e = (h.0 / alpha_x).powi(2) + (h.1 / alpha_y).powi(2) + h.2 * h.2;
+    1.0 / (PI * alpha_x * alpha_y * e * e)
+}
+
+fn ggx_lambda_aniso(v: Vec3, alpha_x: f64, alpha_y: f64) -> f64 {
+    let a2_tan2 = ((alpha_x * v.0).powi(2) + (alpha_y * v.1).powi(2)) / (v.2 * v.2);
+    0.5 * (-1.0 + (1.0 + a2_tan2).sqrt())
+}
+
+pub fn ggx_g1_aniso(v: Vec3, alpha_x: f64, alpha_y: f64) -> f64 {
+    if v.2 <= 0.0 {
+        return 0.0
+    }
+    1.0 / (1.0 + ggx_lambda_aniso(v, alpha_x, alpha_y))
+}
+
+// Height-correlated masking-shadowing for a pair of directions.
+pub fn ggx_g2_aniso(wo: Vec3, wi: Vec3, alpha_x: f64, alpha_y: f64) -> f64 {
+    if wo.2 <= 0.0 || wi.2 <= 0.0 {
+        return 0.0
+    }
+    1.0 / (1.0 + ggx_lambda_aniso(wo, alpha_x, alpha_y) + ggx_lambda_aniso(wi, alpha_x, alpha_y))
+}
+
+// Samples a microfacet normal visible from wo, with pdf G1(wo) max(0, wo.h) D(h) / wo.z.
+pub fn sample_ggx_vndf(wo: Vec3, alpha_x: f64, alpha_y: f64) -> Vec3 {
+    let mut rng = rand::thread_rng();
+    let u1: f64 = rng.gen_range(0.0..1.0);
+    let u2: f64 = rng.gen_range(0.0..1.0);
+
+    let vh = Vec3(alpha_x * wo.0, alpha_y * wo.1, wo.2).unit_vector();
+    let lensq = vh.0 * vh.0 + vh.1 * vh.1;
+    let t1 = if lensq > 0.0 {Vec3(-vh.1, vh.0, 0.0) / lensq.sqrt()} else {Vec3(1.0, 0.0, 0.0)};
+    let t2 = vh.cross(t1);
+
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    let p1 = r * phi.cos();
+    let s = 0.5 * (1.0 + vh.2);
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+    let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+    Vec3(alpha_x * nh.0, alpha_y * nh.1, nh.2.max(0.0)).unit_vector()
+}
+
+pub fn ggx_vndf_pdf(wo: Vec3, h: Vec3, alpha_x: f64, alpha_y: f64) -> f64 {
+    if wo.2 <= 0.0 {
+        return 0.0
+    }
+    ggx_g1_aniso(wo, alpha_x, alpha_y) * wo.dot(h).max(0.0) * ggx_d_aniso(h, alpha_x, alpha_y) / wo.2
+}
+
+/*
+    GTR1 (Berry) distribution, used by the Disney clearcoat lobe
+*/
+
+pub fn gtr1_d(cos_theta_h: f64, alpha: f64) -> f64 {
+    if cos_theta_h <= 0.0 {
+        return 0.0
+    }
+    let a2 = alpha * alpha;
+    (a2 - 1.0) / (PI * a2.ln() * (1.0 + (a2 - 1.0) * cos_theta_h * cos_theta_h))
+}
+
+// Samples a half vector in the local frame with pdf gtr1_d(h.z) h.z.
+pub fn sample_gtr1(alpha: f64) -> Vec3 {
+    let mut rng = rand::thread_rng();
+    let u1: f64 = rng.gen_range(0.0..1.0);
+    let u2: f64 = rng.gen_range(0.0..1.0);
+    let a2 = alpha * alpha;
+    let cos_theta = ((1.0 - a2.powf(1.0 - u1)) / (1.0 - a2)).max(0.0).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u2;
+    Vec3(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+pub fn schlick_weight(cos_theta: f64) -> f64 {
+    (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5)
+}
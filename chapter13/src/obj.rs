@@ -0,0 +1,348 @@
+use std::rc::{Rc};
+use std::collections::{HashMap};
+use std::path::{Path};
+use std::fs;
+use std::io;
+
+use crate::vec3::{Vec3, Color};
+use crate::triangle::{Mesh, TriangleMesh};
+use crate::material::{Material, Lambertian, Metal, Dielectric, MixMaterial, MixWeight};
+
+/*
+    Wavefront OBJ meshes and their MTL materials
+*/
+
+fn invalid(path: &Path, line: usize, message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}: {}", path.display(), line, message))
+}
+
+// Parses between min and max numbers following a statement keyword
+fn numbers(path: &Path, line: usize, args: &[&str], min: usize, max: usize) -> io::Result<Vec<f64>> {
+    if args.len() < min || args.len() > max {
+        let expected = if min == max {format!("{}", min)} else {format!("{} to {}", min, max)};
+        return Err(invalid(path, line, format!("expected {} numbers, found {}", expected, args.len())))
+    }
+    args.iter()
+        .map(|a| a.parse::<f64>().map_err(|_| invalid(path, line, format!("bad number '{}'", a))))
+        .collect()
+}
+
+fn color(path: &Path, line: usize, args: &[&str]) -> io::Result<Color> {
+    let c = numbers(path, line, args, 3, 3)?;
+    Ok(Vec3(c[0], c[1], c[2]))
+}
+
+struct MtlMaterial {
+    kd: Color,
+    ks: Color,
+    ns: f64,
+    ni: Option<f64>,
+    d: f64,
+    illum: u32,
+}
+
+impl MtlMaterial {
+    fn new() -> Self {
+        Self{kd: Vec3(0.8, 0.8, 0.8), ks: Vec3(0.0, 0.0, 0.0), ns: 0.0, ni: None, d: 1.0, illum: 2}
+    }
+
+    // Transparent and refracting illumination models become glass, the mirror models (and a
+    // specular colour with no diffuse) metal, everything else diffuse. The Phong exponent Ns is
+    // turned into fuzz with the usual Blinn-Phong to microfacet roughness match. A dissolve d
+    // below 1 on the other models mixes in glass, keeping the surface with probability d.
+    fn to_material(&self) -> Rc<dyn Material> {
+        let max = |c: Color| c.0.max(c.1).max(c.2);
+        let glass = || Rc::new(Dielectric::new(self.ni.unwrap_or(1.5)));
+        if matches!(self.illum, 4 | 6 | 7 | 9) {
+            return glass()
+        }
+        let surface: Rc<dyn Material> = if matches!(self.illum, 3 | 5 | 8) || (max(self.kd) == 0.0 && max(self.ks) > 0.0) {
+            let fuzz = (2.0 / (self.ns.max(0.0) + 2.0)).sqrt().min(1.0);
+            Rc::new(Metal::new(self.ks, fuzz))
+        } else {
+            Rc::new(Lambertian{albedo: self.kd})
+        };
+        if self.d < 1.0 {
+            Rc::new(MixMaterial{a: glass(), b: surface, weight: MixWeight::Constant(self.d)})
+        } else {
+            surface
+        }
+    }
+}
+
+// Reads the materials of an MTL library by name
+fn load_mtl(path: &Path) -> io::Result<HashMap<String, Rc<dyn Material>>> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    let mut parsed: Vec<(String, MtlMaterial)> = Vec::new();
+
+    for (i, l) in text.lines().enumerate() {
+        let line = i + 1;
+        let mut words = l.split('#').next().unwrap().split_whitespace();
+        let keyword = match words.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        let args: Vec<&str> = words.collect();
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(invalid(path, line, "newmtl without a name".to_string()))
+            }
+            parsed.push((args.join(" "), MtlMaterial::new()));
+            continue
+        }
+        let current = match parsed.last_mut() {
+            Some((_, m)) => m,
+            None => return Err(invalid(path, line, format!("'{}' before any newmtl", keyword))),
+        };
+        match keyword {
+            "Kd" => current.kd = color(path, line, &args)?,
+            "Ks" => current.ks = color(path, line, &args)?,
+            "Ns" => current.ns = numbers(path, line, &args, 1, 1)?[0],
+            "Ni" => current.ni = Some(numbers(path, line, &args, 1, 1)?[0]),
+            "d" => current.d = numbers(path, line, &args, 1, 1)?[0],
+            "Tr" => current.d = 1.0 - numbers(path, line, &args, 1, 1)?[0],
+            "illum" => current.illum = args.first().and_then(|a| a.parse().ok()).filter(|_| args.len() == 1)
+                .ok_or_else(|| invalid(path, line, "illum needs one integer".to_string()))?,
+            // Texture maps, ambient and emissive colours and the like are not used
+            _ => {},
+        }
+    }
+    Ok(parsed.into_iter().map(|(name, m)| (name, m.to_material())).collect())
+}
+
+// Faces collected for one (group, material) pair, with vertices made unique per
+// position/uv/normal combination
+struct GroupBuilder {
+    name: String,
+    material: Option<String>,
+    vertices: HashMap<(usize, Option<usize>, Option<usize>), usize>,
+    corners: Vec<(usize, Option<usize>, Option<usize>)>,
+    triangles: Vec<[usize; 3]>,
+}
+
+impl GroupBuilder {
+    fn vertex(&mut self, corner: (usize, Option<usize>, Option<usize>)) -> usize {
+        let corners = &mut self.corners;
+        *self.vertices.entry(corner).or_insert_with(|| {
+            corners.push(corner);
+            corners.len() - 1
+        })
+    }
+
+    // Uvs and normals are kept only if every vertex of the group has them
+    fn build(self, positions: &[Vec3], uvs: &[(f64, f64)], normals: &[Vec3]) -> Mesh {
+        let all_uvs: Option<Vec<_>> = self.corners.iter().map(|&(_, t, _)| t.map(|t| uvs[t])).collect();
+        let all_normals: Option<Vec<_>> = self.corners.iter().map(|&(_, _, n)| n.map(|n| normals[n])).collect();
+        Mesh{
+            positions: self.corners.iter().map(|&(p, _, _)| positions[p]).collect(),
+            normals: all_normals,
+            uvs: all_uvs,
+            colors: None,
+            triangles: self.triangles,
+        }
+    }
+}
+
+// Resolves a 1-based (or negative, counting back from the end) OBJ index
+fn index(path: &Path, line: usize, s: &str, count: usize, what: &str) -> io::Result<usize> {
+    let i: i64 = s.parse().map_err(|_| invalid(path, line, format!("bad {} index '{}'", what, s)))?;
+    let resolved = if i < 0 {count as i64 + i} else {i - 1};
+    if i == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(invalid(path, line, format!("{} index {} out of range ({} defined)", what, i, count)))
+    }
+    Ok(resolved as usize)
+}
+
+// Loads an OBJ file, with its material libraries resolved next to it. Polygons are triangulated as
+// fans, and there is one mesh for each object or group and material it uses. Faces with no
+// usemtl get a grey diffuse material; statements other than geometry, groups and materials are
+// ignored.
+pub fn load(path: &str) -> io::Result<Vec<TriangleMesh>> {
+    Ok(read(Path::new(path))?.into_iter().map(|(mesh, material)| TriangleMesh::new(mesh, material)).collect())
+}
+
+fn read(path: &Path) -> io::Result<Vec<(Mesh, Rc<dyn Material>)>> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+    let mut materials: HashMap<String, Rc<dyn Material>> = HashMap::new();
+    let mut groups: Vec<GroupBuilder> = Vec::new();
+    let mut name = String::from("default");
+    let mut material: Option<String> = None;
+
+    for (i, l) in text.lines().enumerate() {
+        let line = i + 1;
+        let mut words = l.split('#').next().unwrap().split_whitespace();
+        let keyword = match words.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        let args: Vec<&str> = words.collect();
+        match keyword {
+            "v" => {
+                // An optional w, or the r g b vertex colours some exporters add
+                let p = numbers(path, line, &args, 3, 6)?;
+                positions.push(Vec3(p[0], p[1], p[2]));
+            },
+            "vt" => {
+                let t = numbers(path, line, &args, 1, 3)?;
+                uvs.push((t[0], t.get(1).copied().unwrap_or(0.0)));
+            },
+            "vn" => {
+                let n = numbers(path, line, &args, 3, 3)?;
+                normals.push(Vec3(n[0], n[1], n[2]));
+            },
+            "f" => {
+                if args.len() < 3 {
+                    return Err(invalid(path, line, format!("face needs at least 3 vertices, found {}", args.len())))
+                }
+                let mut corners = Vec::new();
+                for a in &args {
+                    let parts: Vec<&str> = a.split('/').collect();
+                    if parts.len() > 3 {
+                        return Err(invalid(path, line, format!("bad face vertex '{}'", a)))
+                    }
+                    let p = index(path, line, parts[0], positions.len(), "vertex")?;
+                    let t = match parts.get(1) {
+                        Some(s) if !s.is_empty() => Some(index(path, line, s, uvs.len(), "texture coordinate")?),
+                        _ => None,
+                    };
+                    let n = match parts.get(2) {
+                        Some(s) if !s.is_empty() => Some(index(path, line, s, normals.len(), "normal")?),
+                        _ => None,
+                    };
+                    corners.push((p, t, n));
+                }
+
+                let group = match groups.iter().position(|g| g.name == name && g.material == material) {
+                    Some(g) => &mut groups[g],
+                    None => {
+                        groups.push(GroupBuilder{name: name.clone(), material: material.clone(), vertices: HashMap::new(), corners: Vec::new(), triangles: Vec::new()});
+                        groups.last_mut().unwrap()
+                    },
+                };
+                let indices: Vec<usize> = corners.into_iter().map(|c| group.vertex(c)).collect();
+                for k in 1..indices.len() - 1 {
+                    group.triangles.push([indices[0], indices[k], indices[k + 1]]);
+                }
+            },
+            "o" | "g" => name = if args.is_empty() {String::from("default")} else {args.join(" ")},
+            "usemtl" => {
+                let m = args.join(" ");
+                if !materials.contains_key(&m) {
+                    return Err(invalid(path, line, format!("unknown material '{}'", m)))
+                }
+                material = Some(m);
+            },
+            "mtllib" => {
+                for library in &args {
+                    materials.extend(load_mtl(&directory.join(library))?);
+                }
+            },
+            // Smoothing groups, lines, points, free-form geometry and the like
+            _ => {},
+        }
+    }
+
+    let default_material: Rc<dyn Material> = Rc::new(Lambertian{albedo: Vec3(0.8, 0.8, 0.8)});
+    Ok(groups.into_iter().map(|g| {
+        let material = g.material.as_ref().map_or_else(|| default_material.clone(), |m| materials[m].clone());
+        (g.build(&positions, &uvs, &normals), material)
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray::{Ray};
+    use crate::hittable::{HitRecord};
+
+    fn read_str(name: &str, text: &str) -> io::Result<Vec<(Mesh, Rc<dyn Material>)>> {
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, text).unwrap();
+        read(&path)
+    }
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\n";
+
+    #[test]
+    fn negative_indices_count_back_from_the_end() {
+        let groups = read_str("chapter13_negative.obj", &format!("{}f -4 -3 -2 -1\n", SQUARE)).unwrap();
+        assert_eq!(groups.len(), 1);
+        let mesh = &groups[0].0;
+        assert_eq!(mesh.triangles.len(), 2);
+        assert_eq!(mesh.positions[mesh.triangles[1][2]].1, 1.0);
+        assert_eq!(mesh.positions[mesh.triangles[1][2]].0, 0.0);
+    }
+
+    #[test]
+    fn vertex_formats_set_uvs_and_normals() {
+        let cases = [
+            ("f 1 2 3", false, false),
+            ("f 1/1 2/2 3/3", true, false),
+            ("f 1//1 2//1 3//1", false, true),
+            ("f 1/1/1 2/2/1 3/3/1", true, true),
+            // A vertex without a uv leaves the whole group without them
+            ("f 1/1/1 2//1 3/3/1", false, true),
+        ];
+        for (face, has_uvs, has_normals) in cases.iter() {
+            let groups = read_str("chapter13_formats.obj", &format!("{}{}\n", SQUARE, face)).unwrap();
+            let mesh = &groups[0].0;
+            assert_eq!(mesh.uvs.is_some(), *has_uvs, "{}", face);
+            assert_eq!(mesh.normals.is_some(), *has_normals, "{}", face);
+        }
+        let groups = read_str("chapter13_formats.obj", &format!("{}f 1/2 2/3 3/4\n", SQUARE)).unwrap();
+        assert_eq!(groups[0].0.uvs.as_ref().unwrap()[0], (1.0, 0.0));
+    }
+
+    #[test]
+    fn groups_split_the_faces() {
+        let text = format!("{}o a\nf 1 2 3\no b\nf 1 3 4\nf 1 2 4\n", SQUARE);
+        let groups = read_str("chapter13_groups.obj", &text).unwrap();
+        assert_eq!(groups.iter().map(|(m, _)| m.triangles.len()).collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        let cases = [
+            ("usemtl missing\nf 1 2 3\n", "unknown material 'missing'"),
+            ("f 1 2 5\n", "vertex index 5 out of range"),
+            ("f 0 1 2\n", "vertex index 0 out of range"),
+            ("f -5 1 2\n", "vertex index -5 out of range"),
+            ("f 1/9 2 3\n", "texture coordinate index 9 out of range"),
+            ("f 1 2\n", "face needs at least 3 vertices"),
+            ("v 1 x 2\n", "bad number 'x'"),
+        ];
+        for (body, message) in cases.iter() {
+            let err = read_str("chapter13_bad.obj", &format!("{}{}", SQUARE, body)).err().expect(body);
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert!(err.to_string().contains(message), "{}", err);
+        }
+    }
+
+    // Fraction of rays hitting the material head on that pass through it
+    fn transmitted(material: &Rc<dyn Material>) -> f64 {
+        let r = Ray{origin: Vec3(0.0, 1.0, 0.0), direction: Vec3(0.0, -1.0, 0.0), wavelength: None, time: 0.0};
+        let rec = HitRecord::new(Vec3(0.0, 0.0, 0.0), 1.0, &r, Vec3(0.0, 1.0, 0.0), (0.0, 0.0), material.clone());
+        let n = 10_000;
+        let through = (0..n).filter(|_| material.scatter(&r, &rec).is_some_and(|(s, _)| s.direction.1 < 0.0)).count();
+        through as f64 / n as f64
+    }
+
+    #[test]
+    fn dissolve_mixes_in_glass_only_for_opaque_models() {
+        let path = std::env::temp_dir().join("chapter13_dissolve.mtl");
+        fs::write(&path, "newmtl solid\nKd 0.5 0.5 0.5\nnewmtl half\nKd 0.5 0.5 0.5\nd 0.5\n\
+                          newmtl glass\nillum 4\nd 0.5\nNi 1.5\n").unwrap();
+        let materials = load_mtl(&path).unwrap();
+        assert_eq!(transmitted(&materials["solid"]), 0.0);
+        let half = transmitted(&materials["half"]);
+        assert!(half > 0.4 && half < 0.55, "{}", half);
+        assert!(transmitted(&materials["glass"]) > 0.9);
+    }
+}
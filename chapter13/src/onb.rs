@@ -0,0 +1,41 @@
+use crate::vec3::{Vec3};
+
+/*
+    Orthonormal basis built around a direction (w)
+*/
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+
+    pub fn build_from_w(n: Vec3) -> Self {
+        let w = n.unit_vector();
+        let a = if w.0.abs() > 0.9 {Vec3(0.0, 1.0, 0.0)} else {Vec3(1.0, 0.0, 0.0)};
+        let v = w.cross(a).unit_vector();
+        let u = w.cross(v);
+        Self{u, v, w}
+    }
+
+    // Basis with w along the normal and u along the projection of the tangent
+    pub fn from_normal_tangent(n: Vec3, tangent: Vec3) -> Self {
+        let w = n.unit_vector();
+        let t = tangent - tangent.dot(w) * w;
+        if t.is_near_zero() {
+            return Self::build_from_w(w)
+        }
+        let u = t.unit_vector();
+        let v = w.cross(u);
+        Self{u, v, w}
+    }
+
+    pub fn local(&self, a: f64, b: f64, c: f64) -> Vec3 {
+        a*self.u + b*self.v + c*self.w
+    }
+
+    pub fn to_local(&self, a: Vec3) -> Vec3 {
+        Vec3(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+}
@@ -0,0 +1,83 @@
+use rand::prelude::*;
+
+use crate::vec3::{Vec3, Point};
+
+/*
+    Perlin gradient noise
+*/
+
+const POINT_COUNT: usize = 256;
+
+pub struct Perlin {
+    ranvec: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+
+    pub fn new() -> Self {
+        Self{
+            ranvec: (0..POINT_COUNT).map(|_| Vec3::random_range(-1.0..1.0).unit_vector()).collect(),
+            perm_x: Perlin::generate_perm(),
+            perm_y: Perlin::generate_perm(),
+            perm_z: Perlin::generate_perm(),
+        }
+    }
+
+    fn generate_perm() -> Vec<usize> {
+        let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+        p.shuffle(&mut rand::thread_rng());
+        p
+    }
+
+    pub fn noise(&self, p: &Point) -> f64 {
+        let (u, v, w) = (p.0 - p.0.floor(), p.1 - p.1.floor(), p.2 - p.2.floor());
+        let (i, j, k) = (p.0.floor() as i64, p.1.floor() as i64, p.2.floor() as i64);
+
+        let mut c = [[[Vec3(0.0, 0.0, 0.0); 2]; 2]; 2];
+        for (di, plane) in c.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, cell) in row.iter_mut().enumerate() {
+                    let index = self.perm_x[((i + di as i64) & 255) as usize]
+                        ^ self.perm_y[((j + dj as i64) & 255) as usize]
+                        ^ self.perm_z[((k + dk as i64) & 255) as usize];
+                    *cell = self.ranvec[index];
+                }
+            }
+        }
+        Perlin::interpolate(&c, u, v, w)
+    }
+
+    // Trilinear interpolation of the gradients with Hermite smoothing
+    fn interpolate(c: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+        let (uu, vv, ww) = (u*u*(3.0-2.0*u), v*v*(3.0-2.0*v), w*w*(3.0-2.0*w));
+        let mut accum = 0.0;
+        for (i, plane) in c.iter().enumerate() {
+            for (j, row) in plane.iter().enumerate() {
+                for (k, gradient) in row.iter().enumerate() {
+                    let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                    let weight = Vec3(u - fi, v - fj, w - fk);
+                    accum += (fi*uu + (1.0-fi)*(1.0-uu))
+                        * (fj*vv + (1.0-fj)*(1.0-vv))
+                        * (fk*ww + (1.0-fk)*(1.0-ww))
+                        * gradient.dot(weight);
+                }
+            }
+        }
+        accum
+    }
+
+    pub fn turbulence(&self, p: &Point, depth: usize) -> f64 {
+        let mut accum = 0.0;
+        let mut temp_p = *p;
+        let mut weight = 1.0;
+        for _ in 0..depth {
+            accum += weight * self.noise(&temp_p);
+            weight *= 0.5;
+            temp_p *= 2.0;
+        }
+        accum.abs()
+    }
+}
@@ -0,0 +1,245 @@
+use std::rc::{Rc};
+use std::f64::consts::{PI};
+
+use crate::ray::{Ray};
+use crate::vec3::{Vec3, Point};
+use crate::aabb::{Aabb};
+use crate::onb::{Onb};
+use crate::material::{Material};
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::{HittableList};
+
+/*
+    Planes, rectangles, parallelograms, disks and boxes
+*/
+
+// Parameter at which the ray meets the plane through point with the given unit normal
+fn plane_t(r: &Ray, point: Point, normal: Vec3, t_min: f64, t_max: f64) -> Option<f64> {
+    let denom = normal.dot(r.direction);
+    if denom.abs() < 1e-12 {
+        return None
+    }
+    let t = normal.dot(point - r.origin) / denom;
+    if t < t_min || t > t_max {
+        return None
+    }
+    Some(t)
+}
+
+// Infinite plane. Its uvs are distances from point along a basis of the plane, so textures tile;
+// it has no bounding box.
+pub struct Plane {
+    point: Point,
+    // w is the unit normal
+    onb: Onb,
+    material: Rc<dyn Material>,
+}
+
+impl Plane {
+    pub fn new(point: Point, normal: Vec3, material: Rc<dyn Material>) -> Self {
+        Self{point, onb: Onb::build_from_w(normal), material}
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let onb = &self.onb;
+        let t = plane_t(r, self.point, onb.w, t_min, t_max)?;
+        let p = r.at(t);
+        let uv = ((p - self.point).dot(onb.u), (p - self.point).dot(onb.v));
+        Some(HitRecord::new(p, t, r, onb.w, uv, self.material.clone()).with_tangent(onb.u))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+// Axis-aligned rectangles in the plane z = k (XyRect), x = k (YzRect) and y = k (XzRect).
+// Their normals point along the positive axis, and uvs run from 0 to 1 across the first and
+// second coordinate.
+pub struct XyRect {
+    pub x0: f64,
+    pub x1: f64,
+    pub y0: f64,
+    pub y1: f64,
+    pub k: f64,
+    pub material: Rc<dyn Material>,
+}
+
+pub struct YzRect {
+    pub y0: f64,
+    pub y1: f64,
+    pub z0: f64,
+    pub z1: f64,
+    pub k: f64,
+    pub material: Rc<dyn Material>,
+}
+
+pub struct XzRect {
+    pub x0: f64,
+    pub x1: f64,
+    pub z0: f64,
+    pub z1: f64,
+    pub k: f64,
+    pub material: Rc<dyn Material>,
+}
+
+// Shared by the rectangles: axes a and b span the plane, c is its normal
+fn hit_rect(r: &Ray, t_min: f64, t_max: f64, (a, b, c): (usize, usize, usize), [(a0, a1), (b0, b1)]: [(f64, f64); 2], k: f64, material: &Rc<dyn Material>) -> Option<HitRecord> {
+    let t = (k - r.origin[c]) / r.direction[c];
+    if !(t_min..=t_max).contains(&t) {
+        return None
+    }
+    let p = r.at(t);
+    if p[a] < a0 || p[a] > a1 || p[b] < b0 || p[b] > b1 {
+        return None
+    }
+    let axis = |i: usize| Vec3((i == 0) as u8 as f64, (i == 1) as u8 as f64, (i == 2) as u8 as f64);
+    let uv = ((p[a] - a0) / (a1 - a0), (p[b] - b0) / (b1 - b0));
+    Some(HitRecord::new(p, t, r, axis(c), uv, material.clone()).with_tangent(axis(a)))
+}
+
+impl Hittable for XyRect {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        hit_rect(r, t_min, t_max, (0, 1, 2), [(self.x0, self.x1), (self.y0, self.y1)], self.k, &self.material)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&[Vec3(self.x0, self.y0, self.k), Vec3(self.x1, self.y1, self.k)]))
+    }
+}
+
+impl Hittable for YzRect {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        hit_rect(r, t_min, t_max, (1, 2, 0), [(self.y0, self.y1), (self.z0, self.z1)], self.k, &self.material)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&[Vec3(self.k, self.y0, self.z0), Vec3(self.k, self.y1, self.z1)]))
+    }
+}
+
+impl Hittable for XzRect {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        hit_rect(r, t_min, t_max, (0, 2, 1), [(self.x0, self.x1), (self.z0, self.z1)], self.k, &self.material)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&[Vec3(self.x0, self.k, self.z0), Vec3(self.x1, self.k, self.z1)]))
+    }
+}
+
+// Parallelogram with corner q and edges u and v. The normal is u x v, and (u, v) is the position
+// in units of the edges, from 0 to 1.
+pub struct Quad {
+    pub q: Point,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub material: Rc<dyn Material>,
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let n = self.u.cross(self.v);
+        let normal = n.unit_vector();
+        let t = plane_t(r, self.q, normal, t_min, t_max)?;
+        let p = r.at(t);
+
+        // Coordinates of the hit along the edges
+        let w = n / n.dot(n);
+        let planar = p - self.q;
+        let alpha = w.dot(planar.cross(self.v));
+        let beta = w.dot(self.u.cross(planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None
+        }
+        Some(HitRecord::new(p, t, r, normal, (alpha, beta), self.material.clone()).with_tangent(self.u))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let q = self.q;
+        Some(Aabb::from_points(&[q, q + self.u, q + self.v, q + self.u + self.v]))
+    }
+}
+
+// Disk facing along normal. u is the angle around the normal and v the distance from the
+// center, both from 0 to 1.
+pub struct Disk {
+    center: Point,
+    // w is the unit normal
+    onb: Onb,
+    radius: f64,
+    material: Rc<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(center: Point, normal: Vec3, radius: f64, material: Rc<dyn Material>) -> Self {
+        Self{center, onb: Onb::build_from_w(normal), radius, material}
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let onb = &self.onb;
+        let t = plane_t(r, self.center, onb.w, t_min, t_max)?;
+        let p = r.at(t);
+        let d = p - self.center;
+        if d.length_squared() > self.radius * self.radius {
+            return None
+        }
+        let phi = d.dot(onb.v).atan2(d.dot(onb.u)).rem_euclid(2.0 * PI);
+        let uv = (phi / (2.0 * PI), d.length() / self.radius);
+        Some(HitRecord::new(p, t, r, onb.w, uv, self.material.clone()).with_tangent(onb.w.cross(d)))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // Extent of a disk along each axis is radius * sin of the angle between axis and normal
+        let n = self.onb.w;
+        let e = self.radius * Vec3((1.0 - n.0 * n.0).max(0.0).sqrt(), (1.0 - n.1 * n.1).max(0.0).sqrt(), (1.0 - n.2 * n.2).max(0.0).sqrt());
+        Some(Aabb::from_points(&[self.center - e, self.center + e]))
+    }
+}
+
+// Axis-aligned box between two opposite corners, made of six outward-facing quads
+pub struct Cuboid {
+    sides: HittableList,
+    bbox: Aabb,
+}
+
+impl Cuboid {
+    pub fn new(a: Point, b: Point, material: Rc<dyn Material>) -> Self {
+        let min = Vec3(a.0.min(b.0), a.1.min(b.1), a.2.min(b.2));
+        let max = Vec3(a.0.max(b.0), a.1.max(b.1), a.2.max(b.2));
+        let dx = Vec3(max.0 - min.0, 0.0, 0.0);
+        let dy = Vec3(0.0, max.1 - min.1, 0.0);
+        let dz = Vec3(0.0, 0.0, max.2 - min.2);
+
+        let mut sides = HittableList::new();
+        let faces = vec![
+            (Vec3(min.0, min.1, max.2), dx, dy),   // front
+            (Vec3(max.0, min.1, max.2), -dz, dy),  // right
+            (Vec3(max.0, min.1, min.2), -dx, dy),  // back
+            (Vec3(min.0, min.1, min.2), dz, dy),   // left
+            (Vec3(min.0, max.1, max.2), dx, -dz),  // top
+            (Vec3(min.0, min.1, min.2), dx, dz),   // bottom
+        ];
+        for (q, u, v) in faces {
+            sides.add(Box::new(Quad{q, u, v, material: material.clone()}));
+        }
+        Self{sides, bbox: Aabb::from_points(&[min, max])}
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        if !self.bbox.hit(r, t_min, t_max) {
+            return None
+        }
+        self.sides.hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
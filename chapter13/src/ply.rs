@@ -0,0 +1,324 @@
+use std::fs;
+use std::io;
+
+use crate::vec3::{Vec3};
+use crate::triangle::{Mesh};
+
+/*
+    Stanford PLY meshes, in ascii and both binary byte orders
+*/
+
+#[derive(Clone, Copy)]
+enum Scalar {
+    I8, U8, I16, U16, I32, U32, F32, F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(Scalar::I8),
+            "uchar" | "uint8" => Some(Scalar::U8),
+            "short" | "int16" => Some(Scalar::I16),
+            "ushort" | "uint16" => Some(Scalar::U16),
+            "int" | "int32" => Some(Scalar::I32),
+            "uint" | "uint32" => Some(Scalar::U32),
+            "float" | "float32" => Some(Scalar::F32),
+            "double" | "float64" => Some(Scalar::F64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+}
+
+enum Property {
+    Scalar{name: String, ty: Scalar},
+    List{name: String, count: Scalar, item: Scalar},
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    // Bytes of one entry in a binary body when every list is empty
+    fn min_size(&self) -> usize {
+        self.properties.iter().map(|p| match p {
+            Property::Scalar{ty, ..} => ty.size(),
+            Property::List{count, ..} => count.size(),
+        }).sum()
+    }
+}
+
+#[derive(PartialEq)]
+enum Format {
+    Ascii, BinaryLittleEndian, BinaryBigEndian,
+}
+
+// The body of the file, read one value at a time whatever the format
+struct Body<'a> {
+    data: &'a [u8],
+    pos: usize,
+    format: Format,
+}
+
+impl<'a> Body<'a> {
+    fn read(&mut self, ty: Scalar) -> Option<f64> {
+        if self.format == Format::Ascii {
+            while self.pos < self.data.len() && self.data[self.pos].is_ascii_whitespace() {
+                self.pos += 1;
+            }
+            let start = self.pos;
+            while self.pos < self.data.len() && !self.data[self.pos].is_ascii_whitespace() {
+                self.pos += 1;
+            }
+            return std::str::from_utf8(&self.data[start..self.pos]).ok()?.parse().ok()
+        }
+
+        let bytes = self.data.get(self.pos..self.pos + ty.size())?;
+        self.pos += ty.size();
+        let mut b = [0u8; 8];
+        b[..bytes.len()].copy_from_slice(bytes);
+        if self.format == Format::BinaryBigEndian {
+            b[..bytes.len()].reverse();
+        }
+        Some(match ty {
+            Scalar::I8 => b[0] as i8 as f64,
+            Scalar::U8 => b[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes(b),
+        })
+    }
+}
+
+// Loads the vertices and faces of a PLY file as a mesh. Vertices may have normals (nx, ny, nz),
+// colours (red, green, blue) and uvs (u, v, s, t or texture_u, texture_v); integer colours are
+// scaled from 0..255 to 0..1. Polygons are triangulated as fans and other elements are skipped.
+pub fn load(path: &str) -> io::Result<Mesh> {
+    let data = fs::read(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, message));
+
+    if !data.starts_with(b"ply\n") && !data.starts_with(b"ply\r\n") {
+        return Err(invalid("not a PLY file".to_string()))
+    }
+
+    // Header, up to and including the end_header line
+    let mut pos = data.iter().position(|&b| b == b'\n').unwrap() + 1;
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    loop {
+        let end = data[pos..].iter().position(|&b| b == b'\n')
+            .ok_or_else(|| invalid("header has no end_header".to_string()))?;
+        let line = String::from_utf8_lossy(&data[pos..pos + end]).into_owned();
+        pos += end + 1;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", f, "1.0"] => format = Some(match *f {
+                "ascii" => Format::Ascii,
+                "binary_little_endian" => Format::BinaryLittleEndian,
+                "binary_big_endian" => Format::BinaryBigEndian,
+                _ => return Err(invalid(format!("unknown format '{}'", f))),
+            }),
+            ["comment", ..] | ["obj_info", ..] => {},
+            ["element", name, count] => {
+                let count = count.parse().map_err(|_| invalid(format!("bad element count '{}'", count)))?;
+                elements.push(Element{name: name.to_string(), count, properties: Vec::new()});
+            },
+            ["property", "list", count, item, name] => {
+                let (count, item) = Scalar::parse(count).zip(Scalar::parse(item))
+                    .ok_or_else(|| invalid(format!("bad list property '{}'", line.trim())))?;
+                elements.last_mut().ok_or_else(|| invalid("property before any element".to_string()))?
+                    .properties.push(Property::List{name: name.to_string(), count, item});
+            },
+            ["property", ty, name] => {
+                let ty = Scalar::parse(ty).ok_or_else(|| invalid(format!("unknown property type '{}'", ty)))?;
+                elements.last_mut().ok_or_else(|| invalid("property before any element".to_string()))?
+                    .properties.push(Property::Scalar{name: name.to_string(), ty});
+            },
+            ["end_header"] => break,
+            _ => return Err(invalid(format!("bad header line '{}'", line.trim()))),
+        }
+    }
+    let format = format.ok_or_else(|| invalid("header has no format".to_string()))?;
+
+    let mut body = Body{data: &data, pos, format};
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut colors = Vec::new();
+    let mut uvs = Vec::new();
+    let mut triangles = Vec::new();
+    let (mut has_normals, mut has_colors, mut has_uvs) = (false, false, false);
+
+    for element in &elements {
+        if element.count > 0 && element.properties.is_empty() {
+            return Err(invalid(format!("element {} has {} entries but no properties", element.name, element.count)))
+        }
+        if body.format != Format::Ascii && element.count > (data.len() - body.pos) / element.min_size().max(1) {
+            return Err(invalid(format!("element {} has {} entries, more than the rest of the file holds", element.name, element.count)))
+        }
+
+        let has = |names: &[&str]| names.iter().all(|n| element.properties.iter().any(|p| matches!(p, Property::Scalar{name, ..} if name == n)));
+        let uv_names = [["u", "v"], ["s", "t"], ["texture_u", "texture_v"]].iter().copied().find(|names| has(names));
+        if element.name == "vertex" {
+            if !has(&["x", "y", "z"]) {
+                return Err(invalid("vertices have no x, y and z".to_string()))
+            }
+            has_normals = has(&["nx", "ny", "nz"]);
+            has_colors = has(&["red", "green", "blue"]);
+            has_uvs = uv_names.is_some();
+        }
+
+        for i in 0..element.count {
+            let truncated = || invalid(format!("{} {} is truncated or malformed", element.name, i));
+            let mut scalars: Vec<(&str, f64, Scalar)> = Vec::new();
+            let mut face: Option<Vec<usize>> = None;
+            for property in &element.properties {
+                match property {
+                    Property::Scalar{name, ty} => scalars.push((name, body.read(*ty).ok_or_else(truncated)?, *ty)),
+                    Property::List{name, count, item} => {
+                        let n = body.read(*count).ok_or_else(truncated)?;
+                        let values = (0..n as usize).map(|_| body.read(*item)).collect::<Option<Vec<f64>>>().ok_or_else(truncated)?;
+                        if element.name == "face" && (name == "vertex_indices" || name == "vertex_index") {
+                            if let Some(v) = values.iter().find(|&&v| v < 0.0 || v.fract() != 0.0) {
+                                return Err(invalid(format!("face {} has bad vertex index {}", i, v)))
+                            }
+                            face = Some(values.iter().map(|&v| v as usize).collect());
+                        }
+                    },
+                }
+            }
+            let value = |n: &str| scalars.iter().find(|(name, _, _)| *name == n).map_or(0.0, |&(_, v, _)| v);
+
+            if element.name == "vertex" {
+                positions.push(Vec3(value("x"), value("y"), value("z")));
+                if has_normals {
+                    normals.push(Vec3(value("nx"), value("ny"), value("nz")));
+                }
+                if has_colors {
+                    let scale = |n: &str| {
+                        let integer = scalars.iter().any(|(name, _, ty)| *name == n && !matches!(ty, Scalar::F32 | Scalar::F64));
+                        if integer {value(n) / 255.0} else {value(n)}
+                    };
+                    colors.push(Vec3(scale("red"), scale("green"), scale("blue")));
+                }
+                if let Some([u, v]) = uv_names {
+                    uvs.push((value(u), value(v)));
+                }
+            } else if let Some(indices) = face {
+                if indices.len() < 3 {
+                    return Err(invalid(format!("face {} has fewer than 3 vertices", i)))
+                }
+                for k in 1..indices.len() - 1 {
+                    triangles.push([indices[0], indices[k], indices[k + 1]]);
+                }
+            }
+        }
+    }
+
+    if let Some(&i) = triangles.iter().flatten().find(|&&i| i >= positions.len()) {
+        return Err(invalid(format!("face vertex index {} out of range ({} vertices)", i, positions.len())))
+    }
+    Ok(Mesh{
+        positions,
+        normals: if has_normals {Some(normals)} else {None},
+        uvs: if has_uvs {Some(uvs)} else {None},
+        colors: if has_colors {Some(colors)} else {None},
+        triangles,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_bytes(name: &str, data: &[u8]) -> io::Result<Mesh> {
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, data).unwrap();
+        load(path.to_str().unwrap())
+    }
+
+    fn header(format: &str) -> String {
+        format!("ply\nformat {} 1.0\ncomment test\nelement vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+                 property uchar red\nproperty uchar green\nproperty uchar blue\n\
+                 element face 1\nproperty list uchar int vertex_indices\nend_header\n", format)
+    }
+
+    const POSITIONS: [[f32; 3]; 4] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, -2.5]];
+
+    // The quad in binary, in the byte order of the given conversions
+    fn binary(format: &str, f32_bytes: fn(f32) -> [u8; 4], i32_bytes: fn(i32) -> [u8; 4]) -> Vec<u8> {
+        let mut data = header(format).into_bytes();
+        for p in POSITIONS.iter() {
+            for &x in p.iter() {
+                data.extend_from_slice(&f32_bytes(x));
+            }
+            data.extend_from_slice(&[255, 0, 51]);
+        }
+        data.push(4);
+        for i in 0..4 {
+            data.extend_from_slice(&i32_bytes(i));
+        }
+        data
+    }
+
+    fn check_quad(mesh: &Mesh) {
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.positions[3].2, -2.5);
+        assert_eq!(mesh.triangles, vec![[0, 1, 2], [0, 2, 3]]);
+        let c = mesh.colors.as_ref().unwrap()[0];
+        assert!(c.0 == 1.0 && c.1 == 0.0 && (c.2 - 0.2).abs() < 1e-12);
+        assert!(mesh.normals.is_none() && mesh.uvs.is_none());
+    }
+
+    #[test]
+    fn ascii() {
+        let body = "0 0 0 255 0 51\n1 0 0 255 0 51\n1 1 0 255 0 51\n0 1 -2.5 255 0 51\n4 0 1 2 3\n";
+        check_quad(&load_bytes("chapter13_ascii.ply", format!("{}{}", header("ascii"), body).as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn binary_little_endian() {
+        check_quad(&load_bytes("chapter13_le.ply", &binary("binary_little_endian", f32::to_le_bytes, i32::to_le_bytes)).unwrap());
+    }
+
+    #[test]
+    fn binary_big_endian() {
+        check_quad(&load_bytes("chapter13_be.ply", &binary("binary_big_endian", f32::to_be_bytes, i32::to_be_bytes)).unwrap());
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        let vertices = "0 0 0 255 0 51\n1 0 0 255 0 51\n1 1 0 255 0 51\n0 1 -2.5 255 0 51\n";
+        let cases = [
+            (format!("{}{}4 0 1 2 -1\n", header("ascii"), vertices), "bad vertex index -1"),
+            (format!("{}{}3 0 1 1.5\n", header("ascii"), vertices), "bad vertex index 1.5"),
+            (format!("{}{}3 0 1 4\n", header("ascii"), vertices), "index 4 out of range"),
+            (format!("{}{}2 0 1\n", header("ascii"), vertices), "fewer than 3 vertices"),
+            (format!("{}{}", header("ascii"), vertices), "face 0 is truncated"),
+            (header("ascii").replace("end_header\n", ""), "no end_header"),
+            (header("binary_middle_endian"), "unknown format"),
+            ("solid\n".to_string(), "not a PLY file"),
+            (header("ascii").replace("element vertex", "element empty 5\nelement vertex"), "5 entries but no properties"),
+            (header("binary_little_endian").replace("vertex 4", "vertex 4000000000"), "4000000000 entries, more than"),
+        ];
+        for (data, message) in cases.iter() {
+            let err = load_bytes("chapter13_bad.ply", data.as_bytes()).err().expect(message);
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert!(err.to_string().contains(message), "{}", err);
+        }
+    }
+}
@@ -0,0 +1,432 @@
+use crate::vec3::{Vec3, Color};
+
+/*
+    PNG decoding, with the DEFLATE decompressor it needs (RFC 1950, RFC 1951)
+*/
+
+pub const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+// Reads the bit stream least significant bit first, as DEFLATE packs it
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, n: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..n {
+            let byte = *self.data.get(self.pos).ok_or("truncated compressed data")?;
+            value |= (((byte >> self.bit) & 1) as u32) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+// Canonical Huffman code given by its code lengths, decoded one bit at a time
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &l in lengths {
+            counts[l as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for i in 1..16 {
+            offsets[i] = offsets[i - 1] + counts[i - 1];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (s, &l) in lengths.iter().enumerate() {
+            if l != 0 {
+                symbols[offsets[l as usize] as usize] = s as u16;
+                offsets[l as usize] += 1;
+            }
+        }
+        Self{counts, symbols}
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize])
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("bad Huffman code".to_string())
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+// Order in which the code length code lengths are stored
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+fn inflate_block(reader: &mut BitReader, out: &mut Vec<u8>, literals: &Huffman, distances: &Huffman) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        if symbol < 256 {
+            out.push(symbol as u8);
+        } else if symbol == 256 {
+            return Ok(())
+        } else {
+            let s = symbol - 257;
+            if s >= 29 {
+                return Err("bad length code".to_string())
+            }
+            let length = LENGTH_BASE[s] as usize + reader.bits(LENGTH_EXTRA[s] as u32)? as usize;
+            let d = distances.decode(reader)? as usize;
+            if d >= 30 {
+                return Err("bad distance code".to_string())
+            }
+            let distance = DIST_BASE[d] as usize + reader.bits(DIST_EXTRA[d] as u32)? as usize;
+            if distance > out.len() {
+                return Err("distance before start of data".to_string())
+            }
+            let start = out.len() - distance;
+            for i in 0..length {
+                out.push(out[start + i]);
+            }
+        }
+    }
+}
+
+// Decompresses a zlib stream
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 2 || data[0] & 0x0f != 8 || ((data[0] as u16) << 8 | data[1] as u16) % 31 != 0 {
+        return Err("not a zlib stream".to_string())
+    }
+    if data[1] & 0x20 != 0 {
+        return Err("zlib preset dictionaries are not supported".to_string())
+    }
+    let mut reader = BitReader{data: &data[2..], pos: 0, bit: 0};
+    let mut out = Vec::new();
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let header = reader.data.get(reader.pos..reader.pos + 4).ok_or("truncated stored block")?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                reader.pos += 4;
+                let stored = reader.data.get(reader.pos..reader.pos + len).ok_or("truncated stored block")?;
+                out.extend_from_slice(stored);
+                reader.pos += len;
+            },
+            1 => {
+                let mut lengths = [0u8; 288];
+                for (i, l) in lengths.iter_mut().enumerate() {
+                    *l = match i {0..=143 => 8, 144..=255 => 9, 256..=279 => 7, _ => 8};
+                }
+                inflate_block(&mut reader, &mut out, &Huffman::new(&lengths), &Huffman::new(&[5; 30]))?;
+            },
+            2 => {
+                let hlit = reader.bits(5)? as usize + 257;
+                let hdist = reader.bits(5)? as usize + 1;
+                let hclen = reader.bits(4)? as usize + 4;
+                let mut code_lengths = [0u8; 19];
+                for &i in CODE_LENGTH_ORDER.iter().take(hclen) {
+                    code_lengths[i] = reader.bits(3)? as u8;
+                }
+                let code_lengths = Huffman::new(&code_lengths);
+
+                let mut lengths = Vec::with_capacity(hlit + hdist);
+                while lengths.len() < hlit + hdist {
+                    let (value, repeat) = match code_lengths.decode(&mut reader)? {
+                        s @ 0..=15 => (s as u8, 1),
+                        16 => (*lengths.last().ok_or("repeat with no previous length")?, 3 + reader.bits(2)?),
+                        17 => (0, 3 + reader.bits(3)?),
+                        _ => (0, 11 + reader.bits(7)?),
+                    };
+                    lengths.extend(std::iter::repeat(value).take(repeat as usize));
+                }
+                if lengths.len() > hlit + hdist {
+                    return Err("code lengths overrun".to_string())
+                }
+                inflate_block(&mut reader, &mut out, &Huffman::new(&lengths[..hlit]), &Huffman::new(&lengths[hlit..]))?;
+            },
+            _ => return Err("bad block type".to_string()),
+        }
+        if last {
+            return Ok(out)
+        }
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {a} else if pb <= pc {b} else {c}
+}
+
+// Decodes a non-interlaced PNG of any colour type and bit depth to colours in [0, 1] and alpha.
+// Values are returned as stored, without applying gamma or colour profile chunks.
+pub fn decode(data: &[u8]) -> Result<(usize, usize, Vec<Color>, Vec<f64>), String> {
+    if !data.starts_with(&SIGNATURE) {
+        return Err("not a PNG image".to_string())
+    }
+    let mut pos = SIGNATURE.len();
+    let mut header = None;
+    let mut palette: Vec<Color> = Vec::new();
+    let mut palette_alpha: Vec<f64> = Vec::new();
+    let mut compressed = Vec::new();
+    while pos + 8 <= data.len() {
+        let len = u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize;
+        let kind = &data[pos + 4..pos + 8];
+        let body = data.get(pos + 8..pos + 8 + len).ok_or("truncated chunk")?;
+        match kind {
+            b"IHDR" if body.len() == 13 => header = Some((
+                u32::from_be_bytes([body[0], body[1], body[2], body[3]]) as usize,
+                u32::from_be_bytes([body[4], body[5], body[6], body[7]]) as usize,
+                body[8], body[9], body[12],
+            )),
+            b"PLTE" => palette = body.chunks_exact(3).map(|c| Vec3(c[0] as f64, c[1] as f64, c[2] as f64) / 255.0).collect(),
+            b"tRNS" => palette_alpha = body.iter().map(|&a| a as f64 / 255.0).collect(),
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {},
+        }
+        pos += 12 + len;
+    }
+    let (width, height, depth, color_type, interlace) = header.ok_or("missing IHDR chunk")?;
+    if interlace != 0 {
+        return Err("interlaced PNG images are not supported".to_string())
+    }
+    let channels = match color_type {
+        0 => 1, 2 => 3, 3 => 1, 4 => 2, 6 => 4,
+        _ => return Err(format!("bad colour type {}", color_type)),
+    };
+    if !matches!(depth, 1 | 2 | 4 | 8 | 16) || (depth < 8 && !matches!(color_type, 0 | 3)) || (depth == 16 && color_type == 3) {
+        return Err(format!("bad bit depth {} for colour type {}", depth, color_type))
+    }
+
+    // The header is untrusted, so the scanline sizes must not overflow
+    let bits_per_pixel = channels * depth as usize;
+    let bpp = (bits_per_pixel + 7) / 8;
+    let stride = width.checked_mul(bits_per_pixel).and_then(|bits| bits.checked_add(7)).map(|bits| bits / 8);
+    let size = stride.and_then(|s| height.checked_mul(s + 1).map(|size| (s, size)));
+    let (stride, size) = size.ok_or("image is too large")?;
+
+    // Undo the per-scanline filters
+    let raw = inflate(&compressed)?;
+    if raw.len() < size {
+        return Err("truncated image data".to_string())
+    }
+    let mut pixels = vec![0u8; height * stride];
+    for y in 0..height {
+        let filter = raw[y * (stride + 1)];
+        let line = &raw[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+        for x in 0..stride {
+            let a = if x >= bpp {pixels[y * stride + x - bpp]} else {0};
+            let b = if y > 0 {pixels[(y - 1) * stride + x]} else {0};
+            let c = if x >= bpp && y > 0 {pixels[(y - 1) * stride + x - bpp]} else {0};
+            pixels[y * stride + x] = line[x].wrapping_add(match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(format!("bad filter type {}", filter)),
+            });
+        }
+    }
+
+    // Samples of one pixel, scaled to [0, 1] (palette indices are left as integers)
+    let max = ((1u32 << depth) - 1) as f64;
+    let sample = |y: usize, i: usize| -> f64 {
+        let row = &pixels[y * stride..(y + 1) * stride];
+        match depth {
+            16 => u16::from_be_bytes([row[2 * i], row[2 * i + 1]]) as f64 / max,
+            8 => row[i] as f64 / max,
+            _ => {
+                let bit = i * depth as usize;
+                let value = (row[bit / 8] >> (8 - depth as usize - bit % 8)) & ((1 << depth) - 1);
+                value as f64 / max
+            },
+        }
+    };
+    let mut colors = Vec::with_capacity(width * height);
+    let mut alphas = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let s = |c: usize| sample(y, x * channels + c);
+            let (color, alpha) = match color_type {
+                0 => (Vec3(s(0), s(0), s(0)), 1.0),
+                2 => (Vec3(s(0), s(1), s(2)), 1.0),
+                3 => {
+                    let index = (s(0) * max).round() as usize;
+                    let color = *palette.get(index).ok_or("palette index out of range")?;
+                    (color, palette_alpha.get(index).copied().unwrap_or(1.0))
+                },
+                4 => (Vec3(s(0), s(0), s(0)), s(1)),
+                _ => (Vec3(s(0), s(1), s(2)), s(3)),
+            };
+            colors.push(color);
+            alphas.push(alpha);
+        }
+    }
+    Ok((width, height, colors, alphas))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A zlib stream holding data in one stored block, with the checksum left zero as it is unchecked
+    fn stored(data: &[u8]) -> Vec<u8> {
+        let len = data.len() as u16;
+        let mut out = vec![0x78, 0x01, 0x01];
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(data);
+        out.extend_from_slice(&[0; 4]);
+        out
+    }
+
+    fn chunk(out: &mut Vec<u8>, kind: &[u8], body: &[u8]) {
+        out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        out.extend_from_slice(kind);
+        out.extend_from_slice(body);
+        out.extend_from_slice(&[0; 4]);
+    }
+
+    fn png(width: u32, height: u32, depth: u8, color_type: u8, chunks: &[(&[u8], &[u8])], raw: &[u8]) -> Vec<u8> {
+        let mut out = SIGNATURE.to_vec();
+        let mut header = width.to_be_bytes().to_vec();
+        header.extend_from_slice(&height.to_be_bytes());
+        header.extend_from_slice(&[depth, color_type, 0, 0, 0]);
+        chunk(&mut out, b"IHDR", &header);
+        for (kind, body) in chunks {
+            chunk(&mut out, kind, body);
+        }
+        chunk(&mut out, b"IDAT", &stored(raw));
+        chunk(&mut out, b"IEND", &[]);
+        out
+    }
+
+    #[test]
+    fn inflate_stored_block() {
+        let data = [120, 1, 1, 6, 0, 249, 255, 115, 116, 111, 114, 101, 100, 9, 60, 2, 146];
+        assert_eq!(inflate(&data).unwrap(), b"stored");
+    }
+
+    #[test]
+    fn inflate_fixed_huffman_block() {
+        let data = [120, 218, 203, 72, 205, 201, 201, 87, 200, 64, 144, 0, 58, 46, 6, 125];
+        assert_eq!(inflate(&data).unwrap(), b"hello hello hello");
+    }
+
+    #[test]
+    fn inflate_dynamic_huffman_block() {
+        let data = [
+            120, 218, 237, 200, 177, 9, 0, 0, 8, 192, 176, 103, 45, 56, 41, 136, 255, 227, 29, 66, 51,
+            134, 74, 216, 24, 38, 22, 178, 232, 198, 178, 44, 203, 178, 172, 95, 117, 237, 22, 61, 124,
+        ];
+        let expected: Vec<u8> = (0..2000usize).map(|i| b"eeeeeeetttaaoinshrd"[(i * i * 13 + i) % 19]).collect();
+        assert_eq!(inflate(&data).unwrap(), expected);
+    }
+
+    #[test]
+    fn inflate_rejects_malformed_streams() {
+        assert_eq!(inflate(&[0x78, 0x02, 0x01]).unwrap_err(), "not a zlib stream");
+        assert_eq!(inflate(&[0x78, 0x01, 0x01, 6, 0, 249, 255, b's']).unwrap_err(), "truncated stored block");
+        assert_eq!(inflate(&[0x78, 0x01, 0x07]).unwrap_err(), "bad block type");
+        assert_eq!(inflate(&[0x78, 0x01]).unwrap_err(), "truncated compressed data");
+    }
+
+    #[test]
+    fn every_filter_type_is_undone() {
+        // Three RGB pixels per row, one row per filter type, filtered here as an encoder would
+        let (width, height, bpp) = (3, 5, 3);
+        let pixels: Vec<u8> = (0..width * height * bpp).map(|i| (i * 37 % 251) as u8).collect();
+        let stride = width * bpp;
+        let mut raw = Vec::new();
+        for y in 0..height {
+            raw.push(y as u8);
+            for x in 0..stride {
+                let a = if x >= bpp {pixels[y * stride + x - bpp]} else {0};
+                let b = if y > 0 {pixels[(y - 1) * stride + x]} else {0};
+                let c = if x >= bpp && y > 0 {pixels[(y - 1) * stride + x - bpp]} else {0};
+                let prediction = match y {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    _ => paeth(a, b, c),
+                };
+                raw.push(pixels[y * stride + x].wrapping_sub(prediction));
+            }
+        }
+        let (w, h, colors, alphas) = decode(&png(width as u32, height as u32, 8, 2, &[], &raw)).unwrap();
+        assert_eq!((w, h), (width, height));
+        let decoded: Vec<u8> = colors.iter().flat_map(|c| vec![c.0, c.1, c.2]).map(|v| (v * 255.0).round() as u8).collect();
+        assert_eq!(decoded, pixels);
+        assert!(alphas.iter().all(|&a| a == 1.0));
+    }
+
+    #[test]
+    fn paeth_picks_the_closest_neighbour() {
+        assert_eq!(paeth(10, 20, 15), 15);
+        assert_eq!(paeth(10, 20, 12), 20);
+        assert_eq!(paeth(30, 20, 25), 25);
+        assert_eq!(paeth(200, 10, 0), 200);
+    }
+
+    #[test]
+    fn palette_with_transparency() {
+        // 2-bit indices 0, 1, 2, 1; tRNS covers only the first two entries
+        let palette = [255, 0, 0, 0, 255, 0, 0, 0, 255];
+        let (_, _, colors, alphas) = decode(&png(4, 1, 2, 3, &[(b"PLTE", &palette), (b"tRNS", &[0, 128])], &[0, 0b0001_1001])).unwrap();
+        assert_eq!(colors.iter().map(|c| (c.0, c.1, c.2)).collect::<Vec<_>>(),
+                   vec![(1.0, 0.0, 0.0), (0.0, 1.0, 0.0), (0.0, 0.0, 1.0), (0.0, 1.0, 0.0)]);
+        assert_eq!(alphas, vec![0.0, 128.0 / 255.0, 1.0, 128.0 / 255.0]);
+    }
+
+    #[test]
+    fn grey_alpha_sixteen_bit() {
+        let (_, _, colors, alphas) = decode(&png(1, 1, 16, 4, &[], &[0, 0xff, 0xff, 0x80, 0x00])).unwrap();
+        assert_eq!(colors[0].0, 1.0);
+        assert!((alphas[0] - 32768.0 / 65535.0).abs() < 1e-12);
+    }
+
+    fn error(data: &[u8]) -> String {
+        decode(data).err().expect("should fail")
+    }
+
+    #[test]
+    fn malformed_images_are_rejected() {
+        assert_eq!(error(b"GIF89a"), "not a PNG image");
+        assert_eq!(error(&png(1, 1, 8, 0, &[], &[5, 0])), "bad filter type 5");
+        assert_eq!(error(&png(2, 1, 8, 0, &[], &[0, 0])), "truncated image data");
+        assert_eq!(error(&png(1, 1, 8, 3, &[(b"PLTE", &[0, 0, 0])], &[0, 1])), "palette index out of range");
+        assert_eq!(error(&png(1, 1, 4, 2, &[], &[0, 0])), "bad bit depth 4 for colour type 2");
+        assert_eq!(error(&png(0xffffffff, 0xffffffff, 16, 6, &[], &[0])), "image is too large");
+        let mut truncated = png(1, 1, 8, 0, &[], &[0, 0]);
+        truncated.truncate(SIGNATURE.len() + 12);
+        assert_eq!(error(&truncated), "truncated chunk");
+    }
+}
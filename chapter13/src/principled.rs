@@ -0,0 +1,253 @@
+use std::rc::{Rc};
+use std::f64::consts::{PI};
+use rand::prelude::*;
+
+use crate::ray::{Ray};
+use crate::vec3::{Vec3, Color};
+use crate::hittable::{HitRecord};
+use crate::material::{Material, sample_rough_dielectric};
+use crate::microfacet;
+use crate::onb::{Onb};
+use crate::color::{luminance, srgb_to_linear};
+use crate::texture::{Texture};
+
+/*
+    Disney principled BSDF (Burley 2012, "Physically-Based Shading at Disney";
+    Burley 2015, "Extending the Disney BRDF to a BSDF with Integrated Subsurface Scattering")
+*/
+
+#[derive(Clone, Copy)]
+pub struct Principled {
+    pub base_color: Color,
+    pub metallic: f64,
+    pub roughness: f64,
+    pub specular: f64,
+    pub specular_tint: f64,
+    pub anisotropic: f64,
+    pub sheen: f64,
+    pub sheen_tint: f64,
+    pub clearcoat: f64,
+    pub clearcoat_gloss: f64,
+    pub transmission: f64,
+    pub ir: f64,
+}
+
+fn lerp(a: Color, b: Color, t: f64) -> Color {
+    (1.0 - t) * a + t * b
+}
+
+impl Principled {
+
+    pub fn new(base_color: Color) -> Self {
+        Self{
+            base_color,
+            metallic: 0.0,
+            roughness: 0.5,
+            specular: 0.5,
+            specular_tint: 0.0,
+            anisotropic: 0.0,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            clearcoat: 0.0,
+            clearcoat_gloss: 1.0,
+            transmission: 0.0,
+            ir: 1.5,
+        }
+    }
+
+    // Base colour normalized by luminance, isolating hue and saturation
+    fn tint(&self) -> Color {
+        let l = luminance(self.base_color);
+        if l > 0.0 {self.base_color / l} else {Vec3(1.0, 1.0, 1.0)}
+    }
+
+    fn transmission_weight(&self) -> f64 {
+        (1.0 - self.metallic) * self.transmission
+    }
+
+    // Diffuse weight of the reflective part, renormalized for the transmission lobe being chosen separately
+    fn diffuse_weight(&self) -> f64 {
+        let t = self.transmission_weight();
+        if t >= 1.0 {0.0} else {(1.0 - self.metallic) * (1.0 - self.transmission) / (1.0 - t)}
+    }
+
+    fn specular_f0(&self) -> Color {
+        let white = Vec3(1.0, 1.0, 1.0);
+        let dielectric = 0.08 * self.specular * lerp(white, self.tint(), self.specular_tint);
+        lerp(dielectric, self.base_color, self.metallic)
+    }
+
+    fn alphas(&self) -> (f64, f64) {
+        let aspect = (1.0 - 0.9 * self.anisotropic).sqrt();
+        let a = self.roughness * self.roughness;
+        ((a / aspect).max(1e-3), (a * aspect).max(1e-3))
+    }
+
+    fn clearcoat_alpha(&self) -> f64 {
+        0.1 + (0.001 - 0.1) * self.clearcoat_gloss
+    }
+
+    // Probabilities of sampling the diffuse and specular lobes
+    fn lobe_probabilities(&self) -> (f64, f64) {
+        let d = self.diffuse_weight() * luminance(self.base_color).max(0.05);
+        let s = 0.25 + 0.75 * luminance(self.specular_f0());
+        (d / (d + s), s / (d + s))
+    }
+
+    // Probability of sampling the clearcoat before the layers under it
+    fn clearcoat_probability(&self) -> f64 {
+        let c = 0.25 * self.clearcoat;
+        c / (1.0 + c)
+    }
+
+    // Clearcoat BRDF times cos(theta_i) and the pdf of sampling wi from it, in the local frame
+    fn eval_clearcoat(&self, wo: Vec3, wi: Vec3) -> (f64, f64) {
+        let h = (wo + wi).unit_vector();
+        let fh = microfacet::schlick_weight(wi.dot(h));
+        let dc = microfacet::gtr1_d(h.2, self.clearcoat_alpha());
+        let clearcoat = 0.25 * self.clearcoat * dc * (0.04 + 0.96 * fh) * microfacet::ggx_g2_aniso(wo, wi, 0.25, 0.25) / (4.0 * wo.2 * wi.2);
+        (wi.2 * clearcoat, dc * h.2 / (4.0 * wo.dot(h)))
+    }
+
+    // BSDF times cos(theta_i) of the lobes under the clearcoat and the pdf of sampling wi, in the
+    // local frame. The glass lobe reflects by its own Fresnel term, so specular, like diffuse,
+    // covers only the opaque part.
+    fn eval(&self, wo: Vec3, wi: Vec3) -> (Color, f64) {
+        let h = (wo + wi).unit_vector();
+        let cos_d = wi.dot(h);
+        let white = Vec3(1.0, 1.0, 1.0);
+
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+        let fl = microfacet::schlick_weight(wi.2);
+        let fv = microfacet::schlick_weight(wo.2);
+        let fh = microfacet::schlick_weight(cos_d);
+        let diffuse = (1.0 + (fd90 - 1.0) * fl) * (1.0 + (fd90 - 1.0) * fv) / PI * self.base_color;
+        let sheen = self.sheen * fh * lerp(white, self.tint(), self.sheen_tint);
+
+        let (ax, ay) = self.alphas();
+        let f0 = self.specular_f0();
+        let fresnel = f0 + fh * (white - f0);
+        let specular = microfacet::ggx_d_aniso(h, ax, ay) * microfacet::ggx_g2_aniso(wo, wi, ax, ay) / (4.0 * wo.2 * wi.2) * fresnel;
+
+        let f = self.diffuse_weight() * (diffuse + sheen) + specular;
+
+        let (pd, ps) = self.lobe_probabilities();
+        let pdf = pd * wi.2 / PI + ps * microfacet::ggx_vndf_pdf(wo, h, ax, ay) / (4.0 * wo.dot(h));
+        (wi.2 * f, pdf)
+    }
+}
+
+impl Material for Principled {
+
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        let mut rng = rand::thread_rng();
+        let onb = Onb::from_normal_tangent(rec.normal, rec.tangent);
+        let wo = onb.to_local(-r_in.direction.unit_vector());
+
+        // The clearcoat lies over the glass as well as the opaque base, so it is chosen first
+        let pc = if rec.front_face {self.clearcoat_probability()} else {0.0};
+        if pc > 0.0 && rng.gen_range(0.0..1.0) < pc {
+            let h = microfacet::sample_gtr1(self.clearcoat_alpha());
+            let wi = 2.0 * wo.dot(h) * h - wo;
+            if wo.2 <= 0.0 || wi.2 <= 0.0 {
+                return None
+            }
+            let (f_cos, pdf) = self.eval_clearcoat(wo, wi);
+            if pdf <= 0.0 {
+                return None
+            }
+            let weight = f_cos / (pdf * pc);
+            return Some( (Ray{origin: rec.p, direction: onb.local(wi.0, wi.1, wi.2), ..*r_in}, Vec3(weight, weight, weight)) )
+        }
+        let layer_weight = 1.0 / (1.0 - pc);
+
+        // The glass lobe; rays inside a transmissive object only ever see the interface
+        let transmission = self.transmission_weight();
+        if (transmission > 0.0 && !rec.front_face) || rng.gen_range(0.0..1.0) < transmission {
+            let (direction, weight) = sample_rough_dielectric(r_in, rec, self.ir, self.roughness)?;
+            let tint = if direction.dot(rec.normal) < 0.0 && rec.front_face {self.base_color} else {Vec3(1.0, 1.0, 1.0)};
+            return Some( (Ray{origin: rec.p, direction, ..*r_in}, layer_weight * weight * tint) )
+        }
+
+        if wo.2 <= 0.0 {
+            return None
+        }
+
+        let (pd, _) = self.lobe_probabilities();
+        let wi = if rng.gen_range(0.0..1.0) < pd {
+            Vec3::random_cosine_direction()
+        } else {
+            let (ax, ay) = self.alphas();
+            let h = microfacet::sample_ggx_vndf(wo, ax, ay);
+            2.0 * wo.dot(h) * h - wo
+        };
+        if wi.2 <= 0.0 {
+            return None
+        }
+
+        // One-sample MIS over the lobes: the weight uses the combined pdf
+        let (f_cos, pdf) = self.eval(wo, wi);
+        if pdf <= 0.0 {
+            return None
+        }
+        Some( (Ray{origin: rec.p, direction: onb.local(wi.0, wi.1, wi.2), ..*r_in}, layer_weight * f_cos / pdf) )
+    }
+}
+
+// Principled parameters read from textures, as glTF stores them: the base colour texture, which is
+// sRGB-encoded, is decoded and (with any vertex colour) multiplies base_color, and the green and
+// blue channels of metallic_roughness, which is linear, multiply roughness and metallic.
+pub struct TexturedPrincipled {
+    pub principled: Principled,
+    pub base_color: Option<Rc<dyn Texture>>,
+    pub metallic_roughness: Option<Rc<dyn Texture>>,
+}
+
+impl Material for TexturedPrincipled {
+
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        let mut p = self.principled;
+        if let Some(t) = &self.base_color {
+            p.base_color = p.base_color * srgb_to_linear(t.value(rec.u, rec.v, &rec.p));
+        }
+        if let Some(c) = rec.vertex_color {
+            p.base_color = p.base_color * c;
+        }
+        if let Some(t) = &self.metallic_roughness {
+            let mr = t.value(rec.u, rec.v, &rec.p);
+            p.roughness *= mr.1;
+            p.metallic *= mr.2;
+        }
+        p.scatter(r_in, rec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Mean luminance of the weights of rays reflected off a glass slab hit 75 degrees from its normal
+    fn reflected(material: Principled) -> f64 {
+        let material = Rc::new(material);
+        let r = Ray{origin: Vec3(-3.7, 1.0, 0.0), direction: Vec3(3.7, -1.0, 0.0), wavelength: None, time: 0.0};
+        let rec = HitRecord::new(Vec3(0.0, 0.0, 0.0), 1.0, &r, Vec3(0.0, 1.0, 0.0), (0.0, 0.0), material.clone());
+        let n = 100_000;
+        let mut total = 0.0;
+        for _ in 0..n {
+            if let Some((scattered, weight)) = material.scatter(&r, &rec) {
+                if scattered.direction.dot(rec.normal) > 0.0 {
+                    total += luminance(weight);
+                }
+            }
+        }
+        total / n as f64
+    }
+
+    #[test]
+    fn clearcoat_shows_at_full_transmission() {
+        let glass = Principled{transmission: 1.0, roughness: 0.0, ..Principled::new(Vec3(1.0, 1.0, 1.0))};
+        let coated = Principled{clearcoat: 1.0, ..glass};
+        let (bare, coat) = (reflected(glass), reflected(coated));
+        assert!(coat > bare + 0.02, "bare {}, coated {}", bare, coat);
+    }
+}
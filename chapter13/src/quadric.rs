@@ -0,0 +1,444 @@
+use std::rc::{Rc};
+use std::f64::consts::{PI};
+
+use crate::ray::{Ray};
+use crate::vec3::{Vec3, Point};
+use crate::aabb::{Aabb};
+use crate::material::{Material};
+use crate::hittable::{HitRecord, Hittable};
+
+/*
+    Cylinders, cones, paraboloids, hyperboloids and tori, all with the y axis as their axis.
+    On every surface u is the angle around the axis, from +x towards +z, divided by 2 pi.
+*/
+
+// Roots of a t^2 + b t + c = 0 in increasing order, computed without cancellation
+fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None
+        }
+        return Some((-c / b, -c / b))
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None
+    }
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (t0, t1) = if q == 0.0 {(0.0, 0.0)} else {(q / a, c / q)};
+    Some(if t0 < t1 {(t0, t1)} else {(t1, t0)})
+}
+
+// Real roots of x^3 + a x^2 + b x + c = 0, by Cardano's formula (Schwarze, Graphics Gems 1990)
+fn solve_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    const EPSILON: f64 = 1e-12;
+    let sq_a = a * a;
+    let p = (-sq_a / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * sq_a - a * b / 3.0 + c) / 2.0;
+    let cb_p = p * p * p;
+    let d = q * q + cb_p;
+
+    let roots = if d.abs() < EPSILON {
+        if q.abs() < EPSILON {
+            vec![0.0]
+        } else {
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if d < 0.0 {
+        let phi = (-q / (-cb_p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        vec![t * phi.cos(), -t * (phi + PI / 3.0).cos(), -t * (phi - PI / 3.0).cos()]
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![(sqrt_d - q).cbrt() - (sqrt_d + q).cbrt()]
+    };
+    roots.into_iter().map(|y| y - a / 3.0).collect()
+}
+
+// Real roots of x^4 + a x^3 + b x^2 + c x + d = 0 by Ferrari's method, each refined with a few
+// Newton steps on the original polynomial since the closed form loses precision
+fn solve_quartic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    const EPSILON: f64 = 1e-12;
+    // Depressed quartic y^4 + p y^2 + q y + r with x = y - a / 4
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = sq_a * a / 8.0 - a * b / 2.0 + c;
+    let r = -3.0 / 256.0 * sq_a * sq_a + sq_a * b / 16.0 - a * c / 4.0 + d;
+
+    let mut roots = Vec::new();
+    if r.abs() < EPSILON {
+        roots.push(0.0);
+        roots.extend(solve_cubic(0.0, p, q));
+    } else {
+        // One real root of the resolvent cubic splits the quartic into two quadratics
+        let z = solve_cubic(-p / 2.0, -r, r * p / 2.0 - q * q / 8.0)[0];
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+        let u = if u.abs() < EPSILON {0.0} else if u > 0.0 {u.sqrt()} else {return roots};
+        let v = if v.abs() < EPSILON {0.0} else if v > 0.0 {v.sqrt()} else {return roots};
+        let v = if q < 0.0 {-v} else {v};
+        for (bq, cq) in [(v, z - u), (-v, z + u)] {
+            if let Some((y0, y1)) = solve_quadratic(1.0, bq, cq) {
+                roots.push(y0);
+                roots.push(y1);
+            }
+        }
+    }
+
+    roots.into_iter().map(|y| {
+        let mut x = y - a / 4.0;
+        for _ in 0..3 {
+            let f = (((x + a) * x + b) * x + c) * x + d;
+            let df = ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c;
+            if df == 0.0 {
+                break
+            }
+            x -= f / df;
+        }
+        x
+    }).collect()
+}
+
+fn azimuth(p: Point) -> f64 {
+    p.2.atan2(p.0).rem_euclid(2.0 * PI) / (2.0 * PI)
+}
+
+// Direction of increasing azimuth at p
+fn around(p: Point) -> Vec3 {
+    Vec3(-p.2, 0.0, p.0)
+}
+
+// A surface hit in the shape's own frame, with the outward normal
+struct LocalHit {
+    t: f64,
+    p: Point,
+    normal: Vec3,
+    uv: (f64, f64),
+}
+
+impl LocalHit {
+    fn record(self, r: &Ray, material: &Rc<dyn Material>) -> HitRecord {
+        let tangent = around(self.p);
+        HitRecord::new(r.at(self.t), self.t, r, self.normal.unit_vector(), self.uv, material.clone()).with_tangent(tangent)
+    }
+
+    fn closer(a: Option<LocalHit>, b: Option<LocalHit>) -> Option<LocalHit> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(if a.t <= b.t {a} else {b}),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+// Nearest root of the side's quadratic in the ray interval whose hit lies between y0 and y1.
+// The normal is the gradient of the implicit function, given as a closure.
+fn side_hit(o: Point, d: Vec3, (a, b, c): (f64, f64, f64), (y0, y1): (f64, f64), (t_min, t_max): (f64, f64), gradient: impl Fn(Point) -> Vec3) -> Option<LocalHit> {
+    let (t0, t1) = solve_quadratic(a, b, c)?;
+    for t in [t0, t1] {
+        let p = o + t * d;
+        if t >= t_min && t <= t_max && p.1 >= y0 && p.1 <= y1 {
+            return Some(LocalHit{t, p, normal: gradient(p), uv: (azimuth(p), (p.1 - y0) / (y1 - y0))})
+        }
+    }
+    None
+}
+
+// Disk of the given radius closing the shape at height y, facing up or down. v is the distance
+// from the axis relative to the radius.
+fn cap_hit(o: Point, d: Vec3, y: f64, radius: f64, up: bool, (t_min, t_max): (f64, f64)) -> Option<LocalHit> {
+    if d.1 == 0.0 {
+        return None
+    }
+    let t = (y - o.1) / d.1;
+    let p = o + t * d;
+    let r2 = p.0 * p.0 + p.2 * p.2;
+    if t < t_min || t > t_max || r2 > radius * radius {
+        return None
+    }
+    let normal = Vec3(0.0, if up {1.0} else {-1.0}, 0.0);
+    Some(LocalHit{t, p, normal, uv: (azimuth(p), r2.sqrt() / radius)})
+}
+
+// Cylinder standing on center, from there up to height
+pub struct Cylinder {
+    pub center: Point,
+    pub radius: f64,
+    pub height: f64,
+    pub capped: bool,
+    pub material: Rc<dyn Material>,
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (o, d) = (r.origin - self.center, r.direction);
+        let interval = (t_min, t_max);
+        let a = d.0 * d.0 + d.2 * d.2;
+        let b = 2.0 * (o.0 * d.0 + o.2 * d.2);
+        let c = o.0 * o.0 + o.2 * o.2 - self.radius * self.radius;
+        let mut hit = side_hit(o, d, (a, b, c), (0.0, self.height), interval, |p| Vec3(p.0, 0.0, p.2));
+        if self.capped {
+            hit = LocalHit::closer(hit, cap_hit(o, d, 0.0, self.radius, false, interval));
+            hit = LocalHit::closer(hit, cap_hit(o, d, self.height, self.radius, true, interval));
+        }
+        hit.map(|h| h.record(r, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(Aabb::from_points(&[self.center - Vec3(r, 0.0, r), self.center + Vec3(r, self.height, r)]))
+    }
+}
+
+// Cone with its base of the given radius on center and its apex height above
+pub struct Cone {
+    pub center: Point,
+    pub radius: f64,
+    pub height: f64,
+    pub capped: bool,
+    pub material: Rc<dyn Material>,
+}
+
+impl Hittable for Cone {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (o, d) = (r.origin - self.center, r.direction);
+        let interval = (t_min, t_max);
+        // x^2 + z^2 = k^2 (h - y)^2
+        let (h, k) = (self.height, self.radius / self.height);
+        let k2 = k * k;
+        let a = d.0 * d.0 + d.2 * d.2 - k2 * d.1 * d.1;
+        let b = 2.0 * (o.0 * d.0 + o.2 * d.2 + k2 * (h - o.1) * d.1);
+        let c = o.0 * o.0 + o.2 * o.2 - k2 * (h - o.1) * (h - o.1);
+        let mut hit = side_hit(o, d, (a, b, c), (0.0, h), interval, |p| Vec3(p.0, k2 * (h - p.1), p.2));
+        if self.capped {
+            hit = LocalHit::closer(hit, cap_hit(o, d, 0.0, self.radius, false, interval));
+        }
+        hit.map(|h| h.record(r, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(Aabb::from_points(&[self.center - Vec3(r, 0.0, r), self.center + Vec3(r, self.height, r)]))
+    }
+}
+
+// Paraboloid with its vertex on center, opening upwards to the given radius at height
+pub struct Paraboloid {
+    pub center: Point,
+    pub radius: f64,
+    pub height: f64,
+    pub capped: bool,
+    pub material: Rc<dyn Material>,
+}
+
+impl Hittable for Paraboloid {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (o, d) = (r.origin - self.center, r.direction);
+        let interval = (t_min, t_max);
+        // x^2 + z^2 = k y
+        let k = self.radius * self.radius / self.height;
+        let a = d.0 * d.0 + d.2 * d.2;
+        let b = 2.0 * (o.0 * d.0 + o.2 * d.2) - k * d.1;
+        let c = o.0 * o.0 + o.2 * o.2 - k * o.1;
+        let mut hit = side_hit(o, d, (a, b, c), (0.0, self.height), interval, |p| Vec3(2.0 * p.0, -k, 2.0 * p.2));
+        if self.capped {
+            hit = LocalHit::closer(hit, cap_hit(o, d, self.height, self.radius, true, interval));
+        }
+        hit.map(|h| h.record(r, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(Aabb::from_points(&[self.center - Vec3(r, 0.0, r), self.center + Vec3(r, self.height, r)]))
+    }
+}
+
+// Hyperboloid of one sheet with its waist on center, narrowest at waist_radius and widening
+// to end_radius at height / 2 above and below
+pub struct Hyperboloid {
+    pub center: Point,
+    pub waist_radius: f64,
+    pub end_radius: f64,
+    pub height: f64,
+    pub capped: bool,
+    pub material: Rc<dyn Material>,
+}
+
+impl Hittable for Hyperboloid {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (o, d) = (r.origin - self.center, r.direction);
+        let interval = (t_min, t_max);
+        // x^2 + z^2 - k y^2 = w^2
+        let half = self.height / 2.0;
+        let w2 = self.waist_radius * self.waist_radius;
+        let k = (self.end_radius * self.end_radius - w2) / (half * half);
+        let a = d.0 * d.0 + d.2 * d.2 - k * d.1 * d.1;
+        let b = 2.0 * (o.0 * d.0 + o.2 * d.2 - k * o.1 * d.1);
+        let c = o.0 * o.0 + o.2 * o.2 - k * o.1 * o.1 - w2;
+        let mut hit = side_hit(o, d, (a, b, c), (-half, half), interval, |p| Vec3(p.0, -k * p.1, p.2));
+        if self.capped {
+            hit = LocalHit::closer(hit, cap_hit(o, d, -half, self.end_radius, false, interval));
+            hit = LocalHit::closer(hit, cap_hit(o, d, half, self.end_radius, true, interval));
+        }
+        hit.map(|h| h.record(r, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.waist_radius.max(self.end_radius);
+        let half = self.height / 2.0;
+        Some(Aabb::from_points(&[self.center - Vec3(r, half, r), self.center + Vec3(r, half, r)]))
+    }
+}
+
+// Torus around the y axis: a tube of minor_radius swept around a circle of major_radius.
+// v is the angle around the tube, starting from the outer equator and going over the top.
+pub struct Torus {
+    pub center: Point,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+    pub material: Rc<dyn Material>,
+}
+
+impl Hittable for Torus {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (big_r, small_r) = (self.major_radius, self.minor_radius);
+        let length = r.direction.length();
+        let d = r.direction / length;
+        let mut o = r.origin - self.center;
+
+        // Start the quartic near the torus, measuring distance along the unit direction, which
+        // keeps its coefficients well conditioned for distant rays
+        let bound = big_r + small_r;
+        let to_bound = (-o.dot(d) - bound).max(0.0);
+        o += to_bound * d;
+
+        let g = o.dot(o) + big_r * big_r - small_r * small_r;
+        let f = o.dot(d);
+        let four_r2 = 4.0 * big_r * big_r;
+        let roots = solve_quartic(
+            4.0 * f,
+            4.0 * f * f + 2.0 * g - four_r2 * (d.0 * d.0 + d.2 * d.2),
+            4.0 * f * g - 2.0 * four_r2 * (o.0 * d.0 + o.2 * d.2),
+            g * g - four_r2 * (o.0 * o.0 + o.2 * o.2),
+        );
+
+        let t = roots.into_iter()
+            .map(|s| (s + to_bound) / length)
+            .filter(|t| *t >= t_min && *t <= t_max)
+            .fold(None, |best: Option<f64>, t| Some(best.map_or(t, |b| b.min(t))))?;
+        let p = r.origin - self.center + t * r.direction;
+
+        // The normal points away from the nearest point on the central circle
+        let rho = (p.0 * p.0 + p.2 * p.2).sqrt();
+        let ring = if rho > 0.0 {Vec3(p.0, 0.0, p.2) * (big_r / rho)} else {Vec3(big_r, 0.0, 0.0)};
+        let normal = p - ring;
+        let v = normal.1.atan2(rho - big_r).rem_euclid(2.0 * PI) / (2.0 * PI);
+        Some(LocalHit{t, p, normal, uv: (azimuth(p), v)}.record(r, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let e = Vec3(self.major_radius + self.minor_radius, self.minor_radius, self.major_radius + self.minor_radius);
+        Some(Aabb::from_points(&[self.center - e, self.center + e]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Lambertian};
+
+    // Every expected root is found, and every root found solves the polynomial
+    fn check_roots(roots: &[f64], expected: &[f64], polynomial: impl Fn(f64) -> f64, tolerance: f64) {
+        for e in expected {
+            assert!(roots.iter().any(|r| (r - e).abs() < tolerance), "missing root {} in {:?}", e, roots);
+        }
+        for &r in roots {
+            assert!(polynomial(r).abs() < tolerance, "{} is not a root", r);
+        }
+    }
+
+    #[test]
+    fn quadratic() {
+        assert_eq!(solve_quadratic(1.0, 1.0, -6.0), Some((-3.0, 2.0)));
+        assert_eq!(solve_quadratic(-1.0, -1.0, 6.0), Some((-3.0, 2.0)));
+        assert_eq!(solve_quadratic(1.0, -4.0, 4.0), Some((2.0, 2.0)));
+        assert_eq!(solve_quadratic(1.0, 0.0, 0.0), Some((0.0, 0.0)));
+        assert_eq!(solve_quadratic(1.0, 0.0, 1.0), None);
+        // Without cancellation the small root of a badly scaled equation keeps its precision
+        let (t0, t1) = solve_quadratic(1.0, -1e8, 1.0).unwrap();
+        assert!((t0 - 1e-8).abs() < 1e-20 && (t1 - 1e8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn quadratic_with_zero_leading_coefficient() {
+        assert_eq!(solve_quadratic(0.0, 2.0, -4.0), Some((2.0, 2.0)));
+        assert_eq!(solve_quadratic(0.0, 0.0, 1.0), None);
+    }
+
+    #[test]
+    fn cubic() {
+        let cases: [([f64; 3], &[f64]); 5] = [
+            ([-6.0, 11.0, -6.0], &[1.0, 2.0, 3.0]),
+            // (x - 1)^2 (x + 2) and (x - 1)^3
+            ([0.0, -3.0, 2.0], &[1.0, -2.0]),
+            ([-3.0, 3.0, -1.0], &[1.0]),
+            // (x - 2)(x^2 + 1) has one real root
+            ([-2.0, 1.0, -2.0], &[2.0]),
+            ([0.0, 0.0, 0.0], &[0.0]),
+        ];
+        for ([a, b, c], expected) in cases.iter() {
+            let roots = solve_cubic(*a, *b, *c);
+            check_roots(&roots, expected, |x| ((x + a) * x + b) * x + c, 1e-9);
+        }
+        assert_eq!(solve_cubic(-2.0, 1.0, -2.0).len(), 1);
+    }
+
+    #[test]
+    fn quartic() {
+        let cases: [([f64; 4], &[f64]); 6] = [
+            ([-10.0, 35.0, -50.0, 24.0], &[1.0, 2.0, 3.0, 4.0]),
+            // x (x - 1)(x - 2)(x - 3), where the depressed quartic has no constant term
+            ([-6.0, 11.0, -6.0, 0.0], &[0.0, 1.0, 2.0, 3.0]),
+            // (x^2 - 1)(x^2 - 4), which has no odd terms
+            ([0.0, -5.0, 0.0, 4.0], &[-2.0, -1.0, 1.0, 2.0]),
+            // (x - 1)^2 (x - 2)^2 and (x - 1)^2 (x^2 + 1)
+            ([-6.0, 13.0, -12.0, 4.0], &[1.0, 2.0]),
+            ([-2.0, 2.0, -2.0, 1.0], &[1.0]),
+            ([0.0, 0.0, 0.0, 1.0], &[]),
+        ];
+        for ([a, b, c, d], expected) in cases.iter() {
+            let roots = solve_quartic(*a, *b, *c, *d);
+            check_roots(&roots, expected, |x| (((x + a) * x + b) * x + c) * x + d, 1e-6);
+        }
+        assert!(solve_quartic(0.0, 0.0, 0.0, 1.0).is_empty());
+    }
+
+    #[test]
+    fn ray_grazing_the_inner_equator_of_a_torus() {
+        // The hole of the torus has radius 1.5, so rays along x at z = 1.5 touch its inner equator
+        let torus = Torus{center: Vec3(0.0, 0.0, 0.0), major_radius: 2.0, minor_radius: 0.5, material: Rc::new(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})};
+        let ray = |z: f64| Ray{origin: Vec3(-10.0, 0.0, z), direction: Vec3(1.0, 0.0, 0.0), wavelength: None, time: 0.0};
+        let outer = |z: f64| (2.5f64 * 2.5 - z * z).sqrt();
+
+        // Entering the tube from outside
+        let rec = torus.hit(&ray(1.5), 0.0, f64::INFINITY).unwrap();
+        assert!((rec.t - (10.0 - outer(1.5))).abs() < 1e-9);
+        assert!(rec.front_face);
+        assert!((rec.normal - Vec3(-0.8, 0.0, 0.6)).length() < 1e-9);
+
+        // Just inside the hole the ray leaves the tube and enters it again either side of x = 0
+        let z = 1.5 - 1e-3;
+        let inner = (1.5f64 * 1.5 - z * z).sqrt();
+        let first = torus.hit(&ray(z), 0.0, f64::INFINITY).unwrap().t;
+        let second = torus.hit(&ray(z), first + 1e-6, f64::INFINITY).unwrap();
+        assert!((second.t - (10.0 - inner)).abs() < 1e-6, "{}", second.t);
+        assert!(!second.front_face);
+
+        // Just outside the hole the ray stays in the tube until it leaves on the far side
+        let z = 1.5 + 1e-3;
+        let first = torus.hit(&ray(z), 0.0, f64::INFINITY).unwrap().t;
+        let second = torus.hit(&ray(z), first + 1e-6, f64::INFINITY).unwrap();
+        assert!((second.t - (10.0 + outer(z))).abs() < 1e-6, "{}", second.t);
+        assert!(!second.front_face);
+    }
+}
@@ -1,8 +1,13 @@
 use crate::vec3::{Point, Vec3};
 
+#[derive(Clone, Copy)]
 pub struct Ray{
     pub origin: Point,
     pub direction: Vec3,
+    // Set once the path has been collapsed to a single wavelength (in nm)
+    pub wavelength: Option<f64>,
+    // Moment within the camera's shutter interval at which the ray samples the scene
+    pub time: f64,
 }
 
 impl Ray {
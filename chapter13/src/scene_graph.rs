@@ -0,0 +1,119 @@
+use std::rc::{Rc};
+
+use crate::ray::{Ray};
+use crate::aabb::{Aabb};
+use crate::bvh::{BvhNode};
+use crate::material::{Material};
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::{HittableList};
+use crate::transform::{Transform, Transformed};
+
+/*
+    Hierarchy of named nodes, flattened into a BVH for rendering
+*/
+
+// A group of objects and child nodes. The transform applies to the whole subtree, after the
+// transforms of the nodes below; the material replaces that of every object in the subtree
+// unless a node further down sets its own; a hidden node hides its subtree.
+pub struct SceneNode {
+    pub name: String,
+    pub transform: Option<Transform>,
+    pub material: Option<Rc<dyn Material>>,
+    pub visible: bool,
+    pub objects: Vec<Rc<dyn Hittable>>,
+    pub children: Vec<SceneNode>,
+}
+
+impl SceneNode {
+    pub fn new(name: &str) -> Self {
+        Self{
+            name: name.to_string(),
+            transform: None,
+            material: None,
+            visible: true,
+            objects: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, object: Rc<dyn Hittable>) {
+        self.objects.push(object);
+    }
+
+    pub fn add_child(&mut self, child: SceneNode) {
+        self.children.push(child);
+    }
+
+    // The first node with the given name, searching depth first from this one
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut SceneNode> {
+        if self.name == name {
+            return Some(self)
+        }
+        self.children.iter_mut().find_map(|c| c.find_mut(name))
+    }
+
+    // The visible objects, each placed by its composed transform once rather than nested in one
+    // wrapper per level. Bounded objects go into a BVH; unbounded ones such as planes are kept
+    // beside it and tested on every ray.
+    pub fn flatten(&self) -> HittableList {
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        self.collect(None, None, &mut |object| {
+            if object.bounding_box().is_some() {
+                bounded.push(object);
+            } else {
+                unbounded.push(object);
+            }
+        });
+
+        let mut world = HittableList::new();
+        if let Ok(bvh) = BvhNode::new(bounded) {
+            world.add(Box::new(bvh));
+        }
+        for object in unbounded {
+            world.add(object);
+        }
+        world
+    }
+
+    fn collect(&self, parent: Option<Transform>, material: Option<&Rc<dyn Material>>, out: &mut dyn FnMut(Box<dyn Hittable>)) {
+        if !self.visible {
+            return
+        }
+        let transform = match (parent, self.transform) {
+            (Some(p), Some(t)) => Some(p * t),
+            (p, t) => p.or(t),
+        };
+        let material = self.material.as_ref().or(material);
+
+        for object in &self.objects {
+            let mut object = object.clone();
+            if let Some(material) = material {
+                object = Rc::new(MaterialOverride{object, material: material.clone()});
+            }
+            match transform {
+                Some(transform) => out(Box::new(Transformed{object, transform})),
+                None => out(Box::new(object)),
+            }
+        }
+        for child in &self.children {
+            child.collect(transform, material, out);
+        }
+    }
+}
+
+// Reports the object's hits with another material
+struct MaterialOverride {
+    object: Rc<dyn Hittable>,
+    material: Rc<dyn Material>,
+}
+
+impl Hittable for MaterialOverride {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.object.hit(r, t_min, t_max).map(|rec| HitRecord{material: self.material.clone(), ..rec})
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+}
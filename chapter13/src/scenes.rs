@@ -0,0 +1,843 @@
+use std::rc::{Rc};
+
+use rand::prelude::*;
+
+use crate::vec3::{Vec3, Color};
+use crate::ray::{Ray};
+use crate::spectrum::{Emission};
+use crate::principled::{Principled};
+use crate::texture::{SolidColor, CheckerTexture, NoiseTexture, ImageTexture};
+use crate::perlin::{Perlin};
+use crate::bump::{Bumped, Perturbation};
+use crate::hittable::{Hittable, Sphere, MovingSphere, AlphaCutout};
+use crate::planar::{Plane, XyRect, YzRect, XzRect, Quad, Disk, Cuboid};
+use crate::quadric::{Cylinder, Cone, Paraboloid, Hyperboloid, Torus};
+use crate::transform::{Transform, Transformed, Animated, Keyframe, axis_angle};
+use crate::scene_graph::{SceneNode};
+use crate::csg::{Csg, CsgOp};
+use crate::hittable_list::{HittableList};
+use crate::triangle::{Triangle, TriangleMesh, Mesh};
+use crate::camera::{Camera};
+use crate::obj;
+use crate::ply;
+use crate::gltf;
+use crate::material::{Lambertian, Metal, Dielectric, RoughDielectric, Dispersion, DiffuseLight, Coated, MixMaterial, MixWeight, OrenNayar, AnisotropicMetal, ThinFilm, ThinSheet, TwoSided, Subsurface, ShadowCatcher, Material};
+
+/*
+    Demo scenes, selected by name from the command line
+*/
+
+pub enum Background {
+    Sky,
+    Solid(Color),
+}
+
+impl Background {
+    pub fn color(&self, r: &Ray) -> Color {
+        match self {
+            Background::Sky => {
+                let v = 0.5*(r.direction.unit_vector().1 + 1.0);
+                (1.0 - v) * Vec3(1.0, 1.0, 1.0) + v * Vec3(0.5, 0.7, 1.0)
+            }
+            Background::Solid(c) => *c,
+        }
+    }
+}
+
+pub struct Scene {
+    pub world: HittableList,
+    pub camera: Camera,
+    pub background: Background,
+}
+
+pub fn select(name: &str, aspect_ratio: f64) -> Scene {
+    match name {
+        "rough_glass" => rough_glass(aspect_ratio),
+        "coloured_glass" => coloured_glass(aspect_ratio),
+        "dispersion" => dispersion(aspect_ratio),
+        "spectral_lights" => spectral_lights(aspect_ratio),
+        "principled" => principled(aspect_ratio),
+        "coated" => coated(aspect_ratio),
+        "mix" => mix(aspect_ratio),
+        "oren_nayar" => oren_nayar(aspect_ratio),
+        "brushed_metal" => brushed_metal(aspect_ratio),
+        "thin_film" => thin_film(aspect_ratio),
+        "thin_sheet" => thin_sheet(aspect_ratio),
+        "subsurface" => subsurface(aspect_ratio),
+        "bump" => bump(aspect_ratio),
+        "alpha" => alpha(aspect_ratio),
+        "shadow_catcher" => shadow_catcher(aspect_ratio),
+        "lit_shadow_catcher" => lit_shadow_catcher(aspect_ratio),
+        "mesh" => mesh(aspect_ratio),
+        "obj" => obj(aspect_ratio),
+        "ply" => ply(aspect_ratio),
+        "gltf" => gltf(aspect_ratio),
+        "primitives" => primitives(aspect_ratio),
+        "quadrics" => quadrics(aspect_ratio),
+        "instances" => instances(aspect_ratio),
+        "scene_graph" => scene_graph(aspect_ratio),
+        "motion_blur" => motion_blur(aspect_ratio),
+        "csg" => csg(aspect_ratio),
+        "two_sided" => two_sided(aspect_ratio),
+        _ => random_spheres(aspect_ratio),
+    }
+}
+
+// The y = 0 plane most scenes stand on
+fn ground(material: impl Material + 'static) -> Plane {
+    Plane::new(Vec3(0.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0), Rc::new(material))
+}
+
+fn default_camera(aspect_ratio: f64) -> Camera {
+    let lookfrom = Vec3(13.0, 2.0, 3.0);
+    let lookat = Vec3(0.0, 0.0, 0.0);
+    Camera::new(lookfrom, lookat, Vec3(0.0, 1.0, 0.0), 20.0, aspect_ratio, 0.1, 10.0)
+}
+
+pub fn random_spheres(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    let mut rng = rand::thread_rng();
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = rng.gen_range(0.0..1.0);
+            let center = Vec3(a as f64 + 0.9+rng.gen_range(0.0..1.0), 0.2, b as f64 + 0.9*rng.gen_range(0.0..1.0));
+
+            if (center - Vec3(4.0, 0.2, 0.0)).length() > 0.9 {
+                let mat:Rc<dyn Material> = if choose_mat < 0.8 {
+                    Rc::new(Lambertian{albedo: Vec3::random() * Vec3::random()})
+                } else if choose_mat < 0.95 {
+                    Rc::new(Metal::new(Vec3::random_range(0.0..0.5), rng.gen_range(0.0..0.5)))
+                } else {
+                    Rc::new(Dielectric::new(1.5))
+                };
+                world.add(Box::new(Sphere{center, radius:0.2, material: mat}));
+            }
+        }
+    }
+    
+    let material1 = Rc::new(Dielectric::new(1.5));
+    world.add(Box::new(Sphere{center:Vec3(0.0, 1.0, 0.0), radius:1.0, material: material1}));
+
+    let material2 = Rc::new(Lambertian{albedo: Vec3(0.4, 0.2, 0.1)});
+    world.add(Box::new(Sphere{center:Vec3(-4.0, 1.0, 0.0), radius:1.0, material: material2}));
+
+    let material3 = Rc::new(Metal::new(Vec3(0.7, 0.6, 0.5), 0.0));
+    world.add(Box::new(Sphere{center:Vec3(4.0, 1.0, 0.0), radius:1.0, material: material3}));
+
+    Scene{world, camera: default_camera(aspect_ratio), background: Background::Sky}
+}
+
+pub fn rough_glass(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    // Frosted glass with increasing roughness, in front of a coloured backdrop
+    for (i, roughness) in [0.05, 0.2, 0.5].iter().enumerate() {
+        let material = Rc::new(RoughDielectric::new(1.5, *roughness));
+        world.add(Box::new(Sphere{center:Vec3(4.0 - 4.0 * i as f64, 1.0, 0.0), radius:1.0, material}));
+    }
+    for i in -3..4 {
+        let backdrop = Rc::new(Lambertian{albedo: Vec3(0.8, 0.3 + 0.1 * i as f64, 0.1)});
+        world.add(Box::new(Sphere{center:Vec3(-8.0, 0.5, 2.0 * i as f64), radius:0.5, material: backdrop}));
+    }
+
+    Scene{world, camera: default_camera(aspect_ratio), background: Background::Sky}
+}
+
+pub fn coloured_glass(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    // The same absorbing glass at different thicknesses gets darker and more saturated
+    let glass = Rc::new(Dielectric{absorption: Vec3(0.1, 0.6, 1.2), ..Dielectric::new(1.5)});
+    for (i, radius) in [0.25, 0.5, 1.0].iter().enumerate() {
+        world.add(Box::new(Sphere{center:Vec3(4.0 - 4.0 * i as f64, *radius, 0.0), radius:*radius, material: glass.clone()}));
+    }
+
+    // A rough absorbing sphere, like frosted coloured glass
+    let frosted = Rc::new(RoughDielectric{absorption: Vec3(1.0, 0.3, 0.1), ..RoughDielectric::new(1.5, 0.3)});
+    world.add(Box::new(Sphere{center:Vec3(2.0, 0.5, -2.5), radius:0.5, material: frosted}));
+
+    Scene{world, camera: default_camera(aspect_ratio), background: Background::Sky}
+}
+
+pub fn dispersion(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    // Schott BK7 crown glass
+    let bk7 = Rc::new(Dielectric::dispersive(Dispersion::Sellmeier{
+        b: [1.03961212, 0.231792344, 1.01046945],
+        c: [0.00600069867, 0.0200179144, 103.560653],
+    }));
+    world.add(Box::new(Sphere{center:Vec3(4.0, 1.0, 0.0), radius:1.0, material: bk7}));
+
+    // Far more dispersive than any real glass, to make the rainbow fringes obvious
+    let exaggerated = Rc::new(Dielectric::dispersive(Dispersion::Cauchy{a: 1.45, b: 0.05}));
+    world.add(Box::new(Sphere{center:Vec3(0.0, 1.0, 0.0), radius:1.0, material: exaggerated}));
+
+    for i in -5..6 {
+        let stripe = if i % 2 == 0 {Vec3(0.9, 0.9, 0.9)} else {Vec3(0.05, 0.05, 0.05)};
+        world.add(Box::new(Sphere{center:Vec3(-6.0, 0.3, i as f64 * 0.6), radius:0.3, material: Rc::new(Lambertian{albedo: stripe})}));
+    }
+
+    Scene{world, camera: default_camera(aspect_ratio), background: Background::Sky}
+}
+
+pub fn spectral_lights(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    // Candle, tungsten and daylight coloured blackbody lights over white spheres
+    for (i, temperature) in [1900.0, 3200.0, 6500.0].iter().enumerate() {
+        let x = 4.0 - 4.0 * i as f64;
+        let light = Rc::new(DiffuseLight::new(Emission::Blackbody{temperature: *temperature, intensity: 10.0}));
+        world.add(Box::new(Sphere{center:Vec3(x, 3.0, 0.0), radius:0.5, material: light}));
+        world.add(Box::new(Sphere{center:Vec3(x, 1.0, 0.0), radius:1.0, material: Rc::new(Lambertian{albedo: Vec3(0.8, 0.8, 0.8)})}));
+    }
+
+    // Lights can also be given in RGB, which is uplifted to a smooth spectrum
+    let teal = Rc::new(DiffuseLight::new(Emission::Rgb(Vec3(0.5, 3.0, 2.5))));
+    world.add(Box::new(Sphere{center:Vec3(3.0, 0.3, -2.0), radius:0.3, material: teal}));
+
+    let prism = Rc::new(Dielectric::dispersive(Dispersion::Cauchy{a: 1.45, b: 0.05}));
+    world.add(Box::new(Sphere{center:Vec3(2.0, 0.5, 2.0), radius:0.5, material: prism}));
+
+    let camera = Camera::new(Vec3(13.0, 3.0, 3.0), Vec3(0.0, 1.5, 0.0), Vec3(0.0, 1.0, 0.0), 25.0, aspect_ratio, 0.1, 10.0);
+    Scene{world, camera, background: Background::Solid(Vec3(0.01, 0.01, 0.02))}
+}
+
+pub fn principled(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Principled{roughness: 0.8, ..Principled::new(Vec3(0.5, 0.5, 0.5))})));
+
+    let materials = vec![
+        // Glossy plastic
+        Principled{roughness: 0.2, ..Principled::new(Vec3(0.1, 0.3, 0.8))},
+        // Brushed gold
+        Principled{metallic: 1.0, roughness: 0.4, anisotropic: 0.8, ..Principled::new(Vec3(1.0, 0.78, 0.34))},
+        // Car paint
+        Principled{metallic: 0.3, roughness: 0.5, clearcoat: 1.0, ..Principled::new(Vec3(0.6, 0.02, 0.02))},
+        // Velvet-like cloth
+        Principled{roughness: 1.0, specular: 0.0, sheen: 1.0, ..Principled::new(Vec3(0.3, 0.05, 0.3))},
+        // Slightly frosted green glass
+        Principled{roughness: 0.1, transmission: 1.0, ..Principled::new(Vec3(0.7, 1.0, 0.8))},
+    ];
+    for (i, material) in materials.into_iter().enumerate() {
+        let z = -4.4 + 2.2 * i as f64;
+        world.add(Box::new(Sphere{center:Vec3(0.0, 1.0, z), radius:1.0, material: Rc::new(material)}));
+    }
+
+    let camera = Camera::new(Vec3(13.0, 2.0, 3.0), Vec3(0.0, 1.0, 0.0), Vec3(0.0, 1.0, 0.0), 35.0, aspect_ratio, 0.0, 10.0);
+    Scene{world, camera, background: Background::Sky}
+}
+
+pub fn coated(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    // Car paint: a clear, smooth coat over a saturated diffuse base
+    let car_paint = Rc::new(Coated{
+        base: Rc::new(Lambertian{albedo: Vec3(0.7, 0.05, 0.05)}),
+        ir: 1.5,
+        roughness: 0.02,
+        tint: Vec3(1.0, 1.0, 1.0),
+    });
+    world.add(Box::new(Sphere{center:Vec3(4.0, 1.0, 0.0), radius:1.0, material: car_paint}));
+
+    // Varnished wood: a slightly rough amber coat
+    let varnish = Rc::new(Coated{
+        base: Rc::new(Lambertian{albedo: Vec3(0.45, 0.25, 0.12)}),
+        ir: 1.55,
+        roughness: 0.15,
+        tint: Vec3(0.95, 0.8, 0.5),
+    });
+    world.add(Box::new(Sphere{center:Vec3(0.0, 1.0, 0.0), radius:1.0, material: varnish}));
+
+    // Lacquered brushed metal
+    let lacquer = Rc::new(Coated{
+        base: Rc::new(Metal::new(Vec3(0.8, 0.8, 0.85), 0.4)),
+        ir: 1.5,
+        roughness: 0.0,
+        tint: Vec3(0.9, 0.95, 1.0),
+    });
+    world.add(Box::new(Sphere{center:Vec3(-4.0, 1.0, 0.0), radius:1.0, material: lacquer}));
+
+    Scene{world, camera: default_camera(aspect_ratio), background: Background::Sky}
+}
+
+pub fn mix(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    let steel: Rc<dyn Material> = Rc::new(Metal::new(Vec3(0.8, 0.8, 0.8), 0.05));
+    let dirt: Rc<dyn Material> = Rc::new(Lambertian{albedo: Vec3(0.3, 0.22, 0.15)});
+    let paint: Rc<dyn Material> = Rc::new(Lambertian{albedo: Vec3(0.1, 0.4, 0.7)});
+
+    // Dirty metal: a constant fraction of diffuse grime
+    let dirty = Rc::new(MixMaterial{a: steel.clone(), b: dirt, weight: MixWeight::Constant(0.35)});
+    world.add(Box::new(Sphere{center:Vec3(4.0, 1.0, 0.0), radius:1.0, material: dirty}));
+
+    // Partially painted: paint where the checker mask is white
+    let mask = Rc::new(CheckerTexture{
+        odd: Rc::new(SolidColor{color: Vec3(0.0, 0.0, 0.0)}),
+        even: Rc::new(SolidColor{color: Vec3(1.0, 1.0, 1.0)}),
+        scale: 2.0,
+    });
+    let painted = Rc::new(MixMaterial{a: steel, b: paint, weight: MixWeight::Mask(mask)});
+    world.add(Box::new(Sphere{center:Vec3(0.0, 1.0, 0.0), radius:1.0, material: painted}));
+
+    Scene{world, camera: default_camera(aspect_ratio), background: Background::Sky}
+}
+
+pub fn oren_nayar(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.3, 0.3, 0.3)})));
+
+    // From Lambertian to very rough clay; rough spheres look flatter, like the full moon
+    for (i, sigma) in [0.0, 0.35, 1.0].iter().enumerate() {
+        let clay = Rc::new(OrenNayar{albedo: Vec3(0.8, 0.55, 0.4), sigma: *sigma});
+        world.add(Box::new(Sphere{center:Vec3(4.0 - 4.0 * i as f64, 1.0, 0.0), radius:1.0, material: clay}));
+    }
+
+    // A light behind the camera
+    let light = Rc::new(DiffuseLight::new(Emission::Rgb(Vec3(4.0, 4.0, 4.0))));
+    world.add(Box::new(Sphere{center:Vec3(30.0, 12.0, 8.0), radius:10.0, material: light}));
+
+    Scene{world, camera: default_camera(aspect_ratio), background: Background::Solid(Vec3(0.02, 0.02, 0.02))}
+}
+
+pub fn brushed_metal(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    let checker = Rc::new(CheckerTexture{
+        odd: Rc::new(SolidColor{color: Vec3(0.1, 0.1, 0.1)}),
+        even: Rc::new(SolidColor{color: Vec3(0.9, 0.9, 0.9)}),
+        scale: 1.0,
+    });
+    let ground_material = MixMaterial{
+        a: Rc::new(Lambertian{albedo: Vec3(0.0, 0.0, 0.0)}),
+        b: Rc::new(Lambertian{albedo: Vec3(1.0, 1.0, 1.0)}),
+        weight: MixWeight::Mask(checker),
+    };
+    world.add(Box::new(ground(ground_material)));
+
+    // Brushed along the tangent, across it, and isotropic for comparison
+    let aluminium = Vec3(0.91, 0.92, 0.92);
+    let along = Rc::new(AnisotropicMetal{albedo: aluminium, roughness_u: 0.15, roughness_v: 0.6});
+    let across = Rc::new(AnisotropicMetal{albedo: aluminium, roughness_u: 0.6, roughness_v: 0.15});
+    let isotropic = Rc::new(AnisotropicMetal{albedo: aluminium, roughness_u: 0.3, roughness_v: 0.3});
+    world.add(Box::new(Sphere{center:Vec3(4.0, 1.0, 0.0), radius:1.0, material: along}));
+    world.add(Box::new(Sphere{center:Vec3(0.0, 1.0, 0.0), radius:1.0, material: across}));
+    world.add(Box::new(Sphere{center:Vec3(-4.0, 1.0, 0.0), radius:1.0, material: isotropic}));
+
+    Scene{world, camera: default_camera(aspect_ratio), background: Background::Sky}
+}
+
+pub fn thin_film(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.2, 0.2, 0.2)})));
+
+    // Soap bubble: a water film with air on both sides
+    let bubble = Rc::new(Dielectric{film: Some(ThinFilm{thickness: 380.0, ir: 1.33}), ..Dielectric::new(1.0)});
+    world.add(Box::new(Sphere{center:Vec3(4.0, 1.2, 0.0), radius:1.0, material: bubble}));
+
+    // Oil slick over dark metal
+    let oily = Rc::new(Metal{film: Some(ThinFilm{thickness: 520.0, ir: 1.45}), ..Metal::new(Vec3(0.25, 0.25, 0.25), 0.02)});
+    world.add(Box::new(Sphere{center:Vec3(0.0, 1.0, 0.0), radius:1.0, material: oily}));
+
+    // Anti-reflective coating: a quarter-wave of magnesium fluoride on glass
+    let coated_glass = Rc::new(Dielectric{film: Some(ThinFilm{thickness: 100.0, ir: 1.38}), ..Dielectric::new(1.5)});
+    world.add(Box::new(Sphere{center:Vec3(-4.0, 1.0, 0.0), radius:1.0, material: coated_glass}));
+
+    Scene{world, camera: default_camera(aspect_ratio), background: Background::Sky}
+}
+
+pub fn thin_sheet(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    // Paper lampshades around bulbs; the one-sided shade loses the light bouncing around inside it
+    let paper = |two_sided| -> Rc<dyn Material> {
+        let sheet = Rc::new(ThinSheet{reflectance: Vec3(0.45, 0.4, 0.3), transmittance: Vec3(0.5, 0.4, 0.25)});
+        if two_sided {Rc::new(TwoSided{material: sheet})} else {sheet}
+    };
+    for (x, two_sided) in [(2.0, true), (-2.0, false)].iter() {
+        let bulb = Rc::new(DiffuseLight::new(Emission::Blackbody{temperature: 2700.0, intensity: 12.0}));
+        world.add(Box::new(Sphere{center:Vec3(0.0, 1.2, *x), radius:0.2, material: bulb}));
+        world.add(Box::new(Sphere{center:Vec3(0.0, 1.2, *x), radius:1.0, material: paper(*two_sided)}));
+    }
+
+    let camera = Camera::new(Vec3(13.0, 2.0, 3.0), Vec3(0.0, 1.0, 0.0), Vec3(0.0, 1.0, 0.0), 25.0, aspect_ratio, 0.0, 10.0);
+    Scene{world, camera, background: Background::Solid(Vec3(0.02, 0.02, 0.03))}
+}
+
+pub fn subsurface(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.4, 0.4, 0.4)})));
+
+    let wax = Rc::new(Subsurface::new(1.45, Vec3(0.99, 0.97, 0.9), Vec3(0.3, 0.2, 0.1)));
+    let jade = Rc::new(Subsurface::new(1.6, Vec3(0.7, 0.98, 0.8), Vec3(0.15, 0.4, 0.2)));
+    let marble = Rc::new(Subsurface::new(1.5, Vec3(0.99, 0.99, 0.99), Vec3(0.15, 0.15, 0.18)));
+    world.add(Box::new(Sphere{center:Vec3(4.0, 1.0, 0.0), radius:1.0, material: wax}));
+    world.add(Box::new(Sphere{center:Vec3(0.0, 1.0, 0.0), radius:1.0, material: jade}));
+    world.add(Box::new(Sphere{center:Vec3(-4.0, 1.0, 0.0), radius:1.0, material: marble}));
+
+    // Back light, to show light bleeding through the thin parts
+    let light = Rc::new(DiffuseLight::new(Emission::Rgb(Vec3(6.0, 6.0, 6.0))));
+    world.add(Box::new(Sphere{center:Vec3(-6.0, 5.0, -6.0), radius:2.0, material: light}));
+
+    Scene{world, camera: default_camera(aspect_ratio), background: Background::Solid(Vec3(0.1, 0.12, 0.15))}
+}
+
+pub fn bump(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    // Golf ball: dimples from a tangent-space normal map
+    let dimples = Rc::new(ImageTexture::load(concat!(env!("CARGO_MANIFEST_DIR"), "/textures/dimples_normal.ppm")).expect("normal map"));
+    let golf_ball = Rc::new(Bumped{
+        material: Rc::new(Lambertian{albedo: Vec3(0.9, 0.9, 0.9)}),
+        perturbation: Perturbation::NormalMap{map: dimples, strength: 1.0},
+    });
+    world.add(Box::new(Sphere{center:Vec3(4.0, 1.0, 0.0), radius:1.0, material: golf_ball}));
+
+    // Hammered metal: procedural noise as a bump map
+    let hammered = Rc::new(Bumped{
+        material: Rc::new(Metal::new(Vec3(0.8, 0.6, 0.4), 0.05)),
+        perturbation: Perturbation::BumpMap{height: Rc::new(NoiseTexture{noise: Perlin::new(), scale: 4.0}), scale: 0.05},
+    });
+    world.add(Box::new(Sphere{center:Vec3(0.0, 1.0, 0.0), radius:1.0, material: hammered}));
+
+    let plain = Rc::new(Metal::new(Vec3(0.8, 0.6, 0.4), 0.05));
+    world.add(Box::new(Sphere{center:Vec3(-4.0, 1.0, 0.0), radius:1.0, material: plain}));
+
+    Scene{world, camera: default_camera(aspect_ratio), background: Background::Sky}
+}
+
+pub fn alpha(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.6, 0.6, 0.6)})));
+
+    // A cage: a sphere with a checkerboard of holes around a solid ball, casting a patterned shadow
+    let holes = Rc::new(CheckerTexture{
+        odd: Rc::new(SolidColor{color: Vec3(0.0, 0.0, 0.0)}),
+        even: Rc::new(SolidColor{color: Vec3(1.0, 1.0, 1.0)}),
+        scale: 3.0,
+    });
+    let cage = Sphere{center:Vec3(0.0, 1.5, 0.0), radius:1.5, material: Rc::new(Lambertian{albedo: Vec3(0.8, 0.3, 0.1)})};
+    world.add(Box::new(AlphaCutout{object: Box::new(cage), alpha: holes}));
+    world.add(Box::new(Sphere{center:Vec3(0.0, 1.5, 0.0), radius:0.6, material: Rc::new(Metal::new(Vec3(0.8, 0.8, 0.9), 0.1))}));
+
+    // Half-transparent veil: every other ray passes through
+    let veil = Sphere{center:Vec3(3.0, 0.7, 2.5), radius:0.7, material: Rc::new(Lambertian{albedo: Vec3(0.1, 0.3, 0.8)})};
+    world.add(Box::new(AlphaCutout{object: Box::new(veil), alpha: Rc::new(SolidColor{color: Vec3(0.5, 0.5, 0.5)})}));
+
+    let sun = Rc::new(DiffuseLight::new(Emission::Rgb(Vec3(8.0, 8.0, 7.0))));
+    world.add(Box::new(Sphere{center:Vec3(10.0, 20.0, 10.0), radius:5.0, material: sun}));
+
+    Scene{world, camera: default_camera(aspect_ratio), background: Background::Solid(Vec3(0.15, 0.2, 0.3))}
+}
+
+pub fn shadow_catcher(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    // The ground of the backplate photograph
+    world.add(Box::new(ground(ShadowCatcher{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    world.add(Box::new(Sphere{center:Vec3(0.0, 1.0, 0.0), radius:1.0, material: Rc::new(Dielectric::new(1.5))}));
+    world.add(Box::new(Sphere{center:Vec3(-4.0, 1.0, 0.0), radius:1.0, material: Rc::new(Lambertian{albedo: Vec3(0.4, 0.2, 0.1)})}));
+    world.add(Box::new(Sphere{center:Vec3(4.0, 1.0, 0.0), radius:1.0, material: Rc::new(Metal::new(Vec3(0.7, 0.6, 0.5), 0.0))}));
+
+    Scene{world, camera: default_camera(aspect_ratio), background: Background::Sky}
+}
+
+// The same catcher under a lamp in the dark, so all of its light comes from emissive geometry
+pub fn lit_shadow_catcher(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(ShadowCatcher{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    let lamp = Rc::new(DiffuseLight::new(Emission::Rgb(Vec3(8.0, 8.0, 8.0))));
+    world.add(Box::new(Sphere{center:Vec3(2.0, 6.0, 2.0), radius:1.5, material: lamp}));
+    world.add(Box::new(Sphere{center:Vec3(0.0, 1.0, 0.0), radius:1.0, material: Rc::new(Lambertian{albedo: Vec3(0.4, 0.2, 0.1)})}));
+
+    Scene{world, camera: default_camera(aspect_ratio), background: Background::Solid(Vec3(0.0, 0.0, 0.0))}
+}
+
+// Latitude-longitude tessellation of a sphere, with normals and uvs when smooth
+fn tessellated_sphere(center: Vec3, radius: f64, slices: usize, stacks: usize, smooth: bool) -> Mesh {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    for j in 0..=stacks {
+        let v = j as f64 / stacks as f64;
+        let theta = std::f64::consts::PI * (1.0 - v);
+        for i in 0..=slices {
+            let u = i as f64 / slices as f64;
+            let phi = 2.0 * std::f64::consts::PI * u;
+            let n = Vec3(theta.sin() * phi.cos(), theta.cos(), -theta.sin() * phi.sin());
+            positions.push(center + radius * n);
+            normals.push(n);
+            uvs.push((u, v));
+        }
+    }
+    let mut triangles = Vec::new();
+    for j in 0..stacks {
+        for i in 0..slices {
+            let a = j * (slices + 1) + i;
+            let b = a + slices + 1;
+            triangles.push([a, a + 1, b + 1]);
+            triangles.push([a, b + 1, b]);
+        }
+    }
+    if smooth {
+        Mesh{positions, normals: Some(normals), uvs: Some(uvs), colors: None, triangles}
+    } else {
+        Mesh{positions, normals: None, uvs: None, colors: None, triangles}
+    }
+}
+
+pub fn mesh(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    // The same coarse tessellation, faceted and with interpolated normals
+    let faceted = tessellated_sphere(Vec3(-4.0, 1.0, 0.0), 1.0, 16, 8, false);
+    world.add(Box::new(TriangleMesh::new(faceted, Rc::new(Metal::new(Vec3(0.7, 0.6, 0.5), 0.0)))));
+    let smooth = tessellated_sphere(Vec3(0.0, 1.0, 0.0), 1.0, 16, 8, true);
+    world.add(Box::new(TriangleMesh::new(smooth, Rc::new(Metal::new(Vec3(0.7, 0.6, 0.5), 0.0)))));
+
+    // Brushed along the mesh uvs, so the highlight follows the tangent from the texture mapping
+    let brushed = tessellated_sphere(Vec3(4.0, 1.0, 0.0), 1.0, 48, 24, true);
+    world.add(Box::new(TriangleMesh::new(brushed, Rc::new(AnisotropicMetal{albedo: Vec3(0.9, 0.9, 0.9), roughness_u: 0.1, roughness_v: 0.5}))));
+
+    // A lone triangle behind the spheres
+    world.add(Box::new(Triangle{
+        v0: Vec3(-3.0, 0.0, -3.0),
+        v1: Vec3(3.0, 0.0, -3.0),
+        v2: Vec3(0.0, 3.5, -3.0),
+        material: Rc::new(Lambertian{albedo: Vec3(0.2, 0.4, 0.7)}),
+    }));
+
+    Scene{world, camera: default_camera(aspect_ratio), background: Background::Sky}
+}
+
+pub fn obj(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    let props = obj::load(concat!(env!("CARGO_MANIFEST_DIR"), "/models/props.obj")).unwrap_or_else(|e| panic!("{}", e));
+    for mesh in props {
+        world.add(Box::new(mesh));
+    }
+
+    Scene{world, camera: default_camera(aspect_ratio), background: Background::Sky}
+}
+
+pub fn ply(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    // A scanned-looking blob, coloured by its vertex colours through a white Lambertian
+    let mut blob = ply::load(concat!(env!("CARGO_MANIFEST_DIR"), "/models/blob.ply")).unwrap_or_else(|e| panic!("{}", e));
+    for p in blob.positions.iter_mut() {
+        *p += Vec3(0.0, 1.1, 0.0);
+    }
+    world.add(Box::new(TriangleMesh::new(blob, Rc::new(Lambertian{albedo: Vec3(1.0, 1.0, 1.0)}))));
+
+    world.add(Box::new(Sphere{center:Vec3(-4.0, 1.0, 0.0), radius:1.0, material: Rc::new(Lambertian{albedo: Vec3(0.4, 0.2, 0.1)})}));
+    world.add(Box::new(Sphere{center:Vec3(4.0, 1.0, 0.0), radius:1.0, material: Rc::new(Metal::new(Vec3(0.7, 0.6, 0.5), 0.0))}));
+
+    Scene{world, camera: default_camera(aspect_ratio), background: Background::Sky}
+}
+
+pub fn gltf(aspect_ratio: f64) -> Scene {
+    let loaded = gltf::load(concat!(env!("CARGO_MANIFEST_DIR"), "/models/showcase.glb"), aspect_ratio).unwrap_or_else(|e| panic!("{}", e));
+    let mut world = loaded.world;
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    // The camera saved with the scene, if there is one
+    let camera = loaded.cameras.into_iter().next().unwrap_or_else(|| default_camera(aspect_ratio));
+    Scene{world, camera, background: Background::Sky}
+}
+
+pub fn primitives(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    let checker = Rc::new(CheckerTexture{
+        odd: Rc::new(SolidColor{color: Vec3(0.2, 0.3, 0.1)}),
+        even: Rc::new(SolidColor{color: Vec3(0.9, 0.9, 0.9)}),
+        scale: 1.0,
+    });
+    let ground_material = MixMaterial{
+        a: Rc::new(Lambertian{albedo: Vec3(0.2, 0.3, 0.1)}),
+        b: Rc::new(Lambertian{albedo: Vec3(0.9, 0.9, 0.9)}),
+        weight: MixWeight::Mask(checker),
+    };
+    world.add(Box::new(ground(ground_material)));
+
+    // Walls of axis-aligned rectangles behind the objects
+    world.add(Box::new(XyRect{x0: -6.0, x1: 2.0, y0: 0.0, y1: 3.0, k: -3.0, material: Rc::new(Lambertian{albedo: Vec3(0.7, 0.3, 0.3)})}));
+    world.add(Box::new(YzRect{y0: 0.0, y1: 3.0, z0: -3.0, z1: 3.0, k: -6.0, material: Rc::new(Lambertian{albedo: Vec3(0.3, 0.3, 0.7)})}));
+
+    world.add(Box::new(Cuboid::new(Vec3(-4.5, 0.0, -1.0), Vec3(-3.0, 1.5, 0.5), Rc::new(Dielectric::new(1.5)))));
+    world.add(Box::new(Quad{
+        q: Vec3(-1.5, 0.2, -1.0),
+        u: Vec3(1.2, 0.0, 0.8),
+        v: Vec3(0.0, 1.8, 0.3),
+        material: Rc::new(Metal::new(Vec3(0.8, 0.8, 0.9), 0.05)),
+    }));
+    world.add(Box::new(Disk::new(Vec3(2.5, 1.0, 0.5), Vec3(1.0, 0.3, 0.4), 1.0, Rc::new(Lambertian{albedo: Vec3(0.9, 0.6, 0.1)}))));
+
+    // A ceiling light, facing down
+    let light = Rc::new(DiffuseLight::new(Emission::Rgb(Vec3(6.0, 6.0, 6.0))));
+    world.add(Box::new(Quad{q: Vec3(-3.0, 5.0, -1.0), u: Vec3(2.0, 0.0, 0.0), v: Vec3(0.0, 0.0, 2.0), material: light}));
+    world.add(Box::new(XzRect{x0: 4.0, x1: 5.0, z0: -2.0, z1: -1.0, k: 0.01, material: Rc::new(Lambertian{albedo: Vec3(0.1, 0.1, 0.1)})}));
+
+    let camera = Camera::new(Vec3(2.0, 3.0, 9.0), Vec3(-1.5, 1.0, -1.0), Vec3(0.0, 1.0, 0.0), 40.0, aspect_ratio, 0.0, 10.0);
+    Scene{world, camera, background: Background::Solid(Vec3(0.3, 0.35, 0.45))}
+}
+
+pub fn quadrics(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    let checker = Rc::new(CheckerTexture{
+        odd: Rc::new(SolidColor{color: Vec3(0.8, 0.2, 0.1)}),
+        even: Rc::new(SolidColor{color: Vec3(0.9, 0.9, 0.9)}),
+        scale: 8.0,
+    });
+    let striped = Rc::new(MixMaterial{
+        a: Rc::new(Lambertian{albedo: Vec3(0.8, 0.2, 0.1)}),
+        b: Rc::new(Lambertian{albedo: Vec3(0.9, 0.9, 0.9)}),
+        weight: MixWeight::Mask(checker),
+    });
+
+    // Capped solids in the back row, open shells in the front
+    world.add(Box::new(Cylinder{center: Vec3(-4.0, 0.0, -1.5), radius: 0.8, height: 2.0, capped: true, material: Rc::new(Metal::new(Vec3(0.8, 0.8, 0.85), 0.1))}));
+    world.add(Box::new(Cone{center: Vec3(-1.5, 0.0, -1.5), radius: 1.0, height: 2.2, capped: true, material: striped.clone()}));
+    world.add(Box::new(Hyperboloid{center: Vec3(1.2, 1.2, -1.5), waist_radius: 0.5, end_radius: 1.0, height: 2.4, capped: true, material: Rc::new(Lambertian{albedo: Vec3(0.2, 0.4, 0.7)})}));
+    world.add(Box::new(Torus{center: Vec3(4.0, 0.4, -1.5), major_radius: 1.0, minor_radius: 0.4, material: striped}));
+
+    world.add(Box::new(Cylinder{center: Vec3(-2.5, 0.0, 1.5), radius: 0.6, height: 1.2, capped: false, material: Rc::new(Lambertian{albedo: Vec3(0.9, 0.6, 0.1)})}));
+    world.add(Box::new(Paraboloid{center: Vec3(0.0, 0.0, 1.5), radius: 0.8, height: 1.4, capped: false, material: Rc::new(Metal::new(Vec3(0.9, 0.7, 0.4), 0.2))}));
+    world.add(Box::new(Torus{center: Vec3(2.5, 0.9, 1.5), major_radius: 0.6, minor_radius: 0.15, material: Rc::new(Dielectric::new(1.5))}));
+
+    let camera = Camera::new(Vec3(0.0, 4.0, 10.0), Vec3(0.0, 0.8, 0.0), Vec3(0.0, 1.0, 0.0), 40.0, aspect_ratio, 0.0, 10.0);
+    Scene{world, camera, background: Background::Sky}
+}
+
+pub fn instances(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    // One unit box, placed around a ring as pillars of different heights
+    let unit_box: Rc<dyn Hittable> = Rc::new(Cuboid::new(Vec3(-0.5, 0.0, -0.5), Vec3(0.5, 1.0, 0.5), Rc::new(Lambertian{albedo: Vec3(0.7, 0.4, 0.3)})));
+    for i in 0..12 {
+        let angle = 30.0 * i as f64;
+        let height = 0.5 + 0.25 * (i % 4) as f64;
+        let transform = Transform::rotate(Vec3(0.0, 1.0, 0.0), angle)
+            * Transform::translate(Vec3(4.0, 0.0, 0.0))
+            * Transform::rotate(Vec3(0.0, 1.0, 0.0), 15.0)
+            * Transform::scale(Vec3(0.5, height, 0.5));
+        world.add(Box::new(Transformed{object: unit_box.clone(), transform}));
+    }
+
+    // One torus, stood upright by a quarter turn about x and chained through the middle
+    let ring: Rc<dyn Hittable> = Rc::new(Torus{center: Vec3(0.0, 0.0, 0.0), major_radius: 0.6, minor_radius: 0.15, material: Rc::new(Metal::new(Vec3(0.9, 0.7, 0.4), 0.1))});
+    let upright = Transform::rotate_quaternion(std::f64::consts::FRAC_1_SQRT_2, 0.0, 0.0, std::f64::consts::FRAC_1_SQRT_2);
+    for i in 0..5 {
+        let twist = Transform::rotate(Vec3(1.0, 0.0, 0.0), if i % 2 == 0 {0.0} else {90.0});
+        let transform = Transform::translate(Vec3(-1.2 + 0.6 * i as f64, 0.75, 0.0)) * twist * upright;
+        world.add(Box::new(Transformed{object: ring.clone(), transform}));
+    }
+
+    // Non-uniform scale turns a sphere into an ellipsoid, tilted on an arbitrary axis
+    let sphere: Rc<dyn Hittable> = Rc::new(Sphere{center: Vec3(0.0, 0.0, 0.0), radius: 1.0, material: Rc::new(Dielectric::new(1.5))});
+    world.add(Box::new(Transformed{
+        object: sphere,
+        transform: Transform::translate(Vec3(0.0, 0.9, 2.5)) * Transform::rotate(Vec3(1.0, 1.0, 0.0), 40.0) * Transform::scale(Vec3(1.2, 0.4, 0.6)),
+    }));
+
+    let camera = Camera::new(Vec3(0.0, 5.0, 11.0), Vec3(0.0, 0.6, 0.0), Vec3(0.0, 1.0, 0.0), 40.0, aspect_ratio, 0.0, 10.0);
+    Scene{world, camera, background: Background::Sky}
+}
+
+// A table of unit boxes with a vase on top, standing on its origin
+fn table(name: &str, unit_box: &Rc<dyn Hittable>, vase: &Rc<dyn Hittable>) -> SceneNode {
+    let mut table = SceneNode::new(name);
+
+    let mut top = SceneNode::new("top");
+    top.transform = Some(Transform::translate(Vec3(0.0, 1.0, 0.0)) * Transform::scale(Vec3(2.0, 0.1, 1.2)));
+    top.add(unit_box.clone());
+    table.add_child(top);
+
+    let mut legs = SceneNode::new("legs");
+    for (x, z) in [(-0.9, -0.5), (0.9, -0.5), (-0.9, 0.5), (0.9, 0.5)] {
+        let mut leg = SceneNode::new("leg");
+        leg.transform = Some(Transform::translate(Vec3(x, 0.0, z)) * Transform::scale(Vec3(0.1, 1.0, 0.1)));
+        leg.add(unit_box.clone());
+        legs.add_child(leg);
+    }
+    table.add_child(legs);
+
+    // The vase keeps its own material whatever the table is overridden with
+    let mut on_top = SceneNode::new("vase");
+    on_top.transform = Some(Transform::translate(Vec3(0.3, 1.1, 0.0)));
+    on_top.material = Some(Rc::new(Dielectric::new(1.5)));
+    on_top.add(vase.clone());
+    table.add_child(on_top);
+    table
+}
+
+pub fn scene_graph(aspect_ratio: f64) -> Scene {
+    let mut root = SceneNode::new("room");
+    root.add(Rc::new(ground(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    let unit_box: Rc<dyn Hittable> = Rc::new(Cuboid::new(Vec3(-0.5, 0.0, -0.5), Vec3(0.5, 1.0, 0.5), Rc::new(Lambertian{albedo: Vec3(0.6, 0.4, 0.2)})));
+    let vase: Rc<dyn Hittable> = Rc::new(Hyperboloid{center: Vec3(0.0, 0.3, 0.0), waist_radius: 0.12, end_radius: 0.2, height: 0.6, capped: false, material: Rc::new(Lambertian{albedo: Vec3(0.8, 0.8, 0.8)})});
+
+    let mut tables = SceneNode::new("tables");
+    for (i, angle) in vec![-20.0, 0.0, 20.0, 0.0].into_iter().enumerate() {
+        let mut t = table(&format!("table {}", i), &unit_box, &vase);
+        t.transform = Some(Transform::translate(Vec3(-3.9 + 2.6 * i as f64, 0.0, -0.5 * (i % 2) as f64)) * Transform::rotate(Vec3(0.0, 1.0, 0.0), angle));
+        tables.add_child(t);
+    }
+    root.add_child(tables);
+
+    // Restyle one table and hide another after the graph is built
+    if let Some(t) = root.find_mut("table 1") {
+        t.material = Some(Rc::new(Metal::new(Vec3(0.8, 0.8, 0.9), 0.2)));
+    }
+    if let Some(t) = root.find_mut("table 3") {
+        t.visible = false;
+    }
+
+    let camera = Camera::new(Vec3(0.0, 4.0, 9.0), Vec3(0.0, 0.8, 0.0), Vec3(0.0, 1.0, 0.0), 40.0, aspect_ratio, 0.0, 10.0);
+    Scene{world: root.flatten(), camera, background: Background::Sky}
+}
+
+pub fn motion_blur(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    // Balls bouncing up by different amounts while the shutter is open
+    for i in 0..5 {
+        let x = -4.0 + 2.0 * i as f64;
+        let center0 = Vec3(x, 0.5, 1.5);
+        world.add(Box::new(MovingSphere{
+            center0,
+            center1: center0 + Vec3(0.0, 0.2 * i as f64, 0.0),
+            time0: 0.0,
+            time1: 1.0,
+            radius: 0.5,
+            material: Rc::new(Lambertian{albedo: Vec3(0.2 + 0.15 * i as f64, 0.3, 0.8 - 0.15 * i as f64)}),
+        }));
+    }
+
+    // A box spinning a quarter turn, and a ring sliding past as it grows and tips over
+    let unit_box: Rc<dyn Hittable> = Rc::new(Cuboid::new(Vec3(-0.5, 0.0, -0.5), Vec3(0.5, 1.0, 0.5), Rc::new(Lambertian{albedo: Vec3(0.7, 0.4, 0.3)})));
+    world.add(Box::new(Animated::new(unit_box, vec![
+        Keyframe{time: 0.0, translation: Vec3(-2.0, 0.0, -1.0), rotation: axis_angle(Vec3(0.0, 1.0, 0.0), 0.0), scale: Vec3(1.5, 1.5, 1.5)},
+        Keyframe{time: 1.0, translation: Vec3(-2.0, 0.0, -1.0), rotation: axis_angle(Vec3(0.0, 1.0, 0.0), 90.0), scale: Vec3(1.5, 1.5, 1.5)},
+    ])));
+    let ring: Rc<dyn Hittable> = Rc::new(Torus{center: Vec3(0.0, 0.0, 0.0), major_radius: 0.8, minor_radius: 0.2, material: Rc::new(Metal::new(Vec3(0.9, 0.7, 0.4), 0.1))});
+    world.add(Box::new(Animated::new(ring, vec![
+        Keyframe{time: 0.0, translation: Vec3(1.0, 1.0, -1.0), rotation: axis_angle(Vec3(1.0, 0.0, 0.0), 90.0), scale: Vec3(0.8, 0.8, 0.8)},
+        Keyframe{time: 0.5, translation: Vec3(2.0, 1.2, -1.0), rotation: axis_angle(Vec3(1.0, 0.0, 0.0), 60.0), scale: Vec3(1.0, 1.0, 1.0)},
+        Keyframe{time: 1.0, translation: Vec3(3.0, 1.4, -1.0), rotation: axis_angle(Vec3(1.0, 0.0, 0.0), 30.0), scale: Vec3(1.2, 1.2, 1.2)},
+    ])));
+
+    let camera = Camera::new(Vec3(0.0, 3.0, 10.0), Vec3(0.0, 0.8, 0.0), Vec3(0.0, 1.0, 0.0), 40.0, aspect_ratio, 0.0, 10.0)
+        .with_shutter(0.0, 1.0);
+    Scene{world, camera, background: Background::Sky}
+}
+
+pub fn csg(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    // The classic figure: a rounded cube, the overlap of a box and a sphere, drilled through
+    // along all three axes by one cylinder turned three ways
+    let rounded = Csg{
+        op: CsgOp::Intersection,
+        a: Box::new(Cuboid::new(Vec3(-0.8, -0.8, -0.8), Vec3(0.8, 0.8, 0.8), Rc::new(Lambertian{albedo: Vec3(0.8, 0.3, 0.2)}))),
+        b: Box::new(Sphere{center: Vec3(0.0, 0.0, 0.0), radius: 1.05, material: Rc::new(Lambertian{albedo: Vec3(0.2, 0.4, 0.8)})}),
+    };
+    let drill: Rc<dyn Hittable> = Rc::new(Cylinder{center: Vec3(0.0, -1.5, 0.0), radius: 0.45, height: 3.0, capped: true, material: Rc::new(Lambertian{albedo: Vec3(0.2, 0.7, 0.3)})});
+    let cross = Csg{
+        op: CsgOp::Union,
+        a: Box::new(drill.clone()),
+        b: Box::new(Csg{
+            op: CsgOp::Union,
+            a: Box::new(Transformed{object: drill.clone(), transform: Transform::rotate(Vec3(1.0, 0.0, 0.0), 90.0)}),
+            b: Box::new(Transformed{object: drill, transform: Transform::rotate(Vec3(0.0, 0.0, 1.0), 90.0)}),
+        }),
+    };
+    let figure: Rc<dyn Hittable> = Rc::new(Csg{op: CsgOp::Difference, a: Box::new(rounded), b: Box::new(cross)});
+    world.add(Box::new(Transformed{object: figure, transform: Transform::translate(Vec3(-2.5, 1.0, 0.0)) * Transform::rotate(Vec3(0.0, 1.0, 0.0), 30.0)}));
+
+    // A biconvex glass lens, where two large spheres overlap
+    world.add(Box::new(Csg{
+        op: CsgOp::Intersection,
+        a: Box::new(Sphere{center: Vec3(0.0, 1.1, -0.9), radius: 1.5, material: Rc::new(Dielectric::new(1.5))}),
+        b: Box::new(Sphere{center: Vec3(0.0, 1.1, 1.3), radius: 1.5, material: Rc::new(Dielectric::new(1.5))}),
+    }));
+
+    // A sphere with a wedge cut out by a box over one corner
+    world.add(Box::new(Csg{
+        op: CsgOp::Difference,
+        a: Box::new(Sphere{center: Vec3(2.5, 1.0, 0.0), radius: 1.0, material: Rc::new(Metal::new(Vec3(0.8, 0.8, 0.9), 0.05))}),
+        b: Box::new(Cuboid::new(Vec3(2.5, 1.0, 0.0), Vec3(4.0, 2.5, 1.5), Rc::new(Lambertian{albedo: Vec3(0.9, 0.6, 0.1)}))),
+    }));
+
+    let camera = Camera::new(Vec3(0.0, 3.5, 9.0), Vec3(0.0, 0.9, 0.0), Vec3(0.0, 1.0, 0.0), 40.0, aspect_ratio, 0.0, 10.0);
+    Scene{world, camera, background: Background::Sky}
+}
+
+pub fn two_sided(aspect_ratio: f64) -> Scene {
+    let mut world = HittableList::new();
+
+    world.add(Box::new(ground(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})));
+
+    // A glowing ceiling plane whose normal points up, away from the scene: one-sided, it would
+    // only light the sky
+    let glow = Rc::new(DiffuseLight::new(Emission::Rgb(Vec3(0.6, 0.6, 0.7))));
+    world.add(Box::new(Plane::new(Vec3(0.0, 4.0, 0.0), Vec3(0.0, 1.0, 0.0), Rc::new(TwoSided{material: glow}))));
+
+    // Two light panels facing away from the camera; only the two-sided one shows from behind
+    let panel = Rc::new(DiffuseLight::new(Emission::Rgb(Vec3(4.0, 2.5, 1.0))));
+    world.add(Box::new(Quad{q: Vec3(-3.0, 0.5, -1.0), u: Vec3(0.0, 2.0, 0.0), v: Vec3(2.0, 0.0, 0.0), material: panel.clone()}));
+    world.add(Box::new(Quad{q: Vec3(1.0, 0.5, -1.0), u: Vec3(0.0, 2.0, 0.0), v: Vec3(2.0, 0.0, 0.0), material: Rc::new(TwoSided{material: panel})}));
+
+    world.add(Box::new(Sphere{center: Vec3(-2.0, 0.5, 1.0), radius: 0.5, material: Rc::new(Lambertian{albedo: Vec3(0.8, 0.8, 0.8)})}));
+    world.add(Box::new(Sphere{center: Vec3(2.0, 0.5, 1.0), radius: 0.5, material: Rc::new(Lambertian{albedo: Vec3(0.8, 0.8, 0.8)})}));
+
+    let camera = Camera::new(Vec3(0.0, 2.0, 8.0), Vec3(0.0, 1.2, 0.0), Vec3(0.0, 1.0, 0.0), 45.0, aspect_ratio, 0.0, 10.0);
+    Scene{world, camera, background: Background::Solid(Vec3(0.0, 0.0, 0.0))}
+}
@@ -0,0 +1,282 @@
+use std::ops;
+use rand::prelude::*;
+
+use crate::vec3::{Vec3, Color};
+
+/*
+    Spectral utility functions
+*/
+
+pub const LAMBDA_MIN: f64 = 380.0;
+pub const LAMBDA_MAX: f64 = 780.0;
+
+// Wavelengths standing in for the red, green and blue channels
+pub const RGB_WAVELENGTHS: [f64; 3] = [630.0, 532.0, 465.0];
+
+pub fn sample_wavelength() -> f64 {
+    rand::thread_rng().gen_range(LAMBDA_MIN..LAMBDA_MAX)
+}
+
+fn piecewise_gaussian(x: f64, mu: f64, sigma1: f64, sigma2: f64) -> f64 {
+    let t = (x - mu) / if x < mu {sigma1} else {sigma2};
+    (-0.5 * t * t).exp()
+}
+
+// CIE 1931 colour matching functions, multi-lobe fit by Wyman, Sloan and Shirley (2013).
+pub fn cie_xyz(lambda: f64) -> Vec3 {
+    Vec3(
+        1.056 * piecewise_gaussian(lambda, 599.8, 37.9, 31.0)
+            + 0.362 * piecewise_gaussian(lambda, 442.0, 16.0, 26.7)
+            - 0.065 * piecewise_gaussian(lambda, 501.1, 20.4, 26.2),
+        0.821 * piecewise_gaussian(lambda, 568.8, 46.9, 40.5)
+            + 0.286 * piecewise_gaussian(lambda, 530.9, 16.3, 31.1),
+        1.217 * piecewise_gaussian(lambda, 437.0, 11.8, 36.0)
+            + 0.681 * piecewise_gaussian(lambda, 459.0, 26.0, 13.8),
+    )
+}
+
+pub fn xyz_to_linear_srgb(xyz: Vec3) -> Color {
+    Vec3(
+        3.2406 * xyz.0 - 1.5372 * xyz.1 - 0.4986 * xyz.2,
+        -0.9689 * xyz.0 + 1.8758 * xyz.1 + 0.0415 * xyz.2,
+        0.0557 * xyz.0 - 0.2040 * xyz.1 + 1.0570 * xyz.2,
+    )
+}
+
+// Per-channel scale that makes a uniformly distributed wavelength average to white: the
+// reciprocal of the mean of xyz_to_linear_srgb(cie_xyz(lambda)) over [LAMBDA_MIN, LAMBDA_MAX).
+const WHITE_BALANCE: [f64; 3] = [3.116170, 3.938999, 4.121606];
+
+// The same for the clamped weights of wavelength_to_rgb
+const CLAMPED_WHITE_BALANCE: [f64; 3] = [2.270371, 3.466611, 3.659781];
+
+fn white_balance() -> Color {
+    Vec3(WHITE_BALANCE[0], WHITE_BALANCE[1], WHITE_BALANCE[2])
+}
+
+// RGB weight of a single wavelength sample drawn uniformly from [LAMBDA_MIN, LAMBDA_MAX).
+// Spectral colours outside the sRGB gamut have negative channels, which would make a weight
+// that throughput cannot carry, so they are clamped and the clamped weights averaged to white.
+pub fn wavelength_to_rgb(lambda: f64) -> Color {
+    let rgb = xyz_to_linear_srgb(cie_xyz(lambda));
+    Vec3(
+        rgb.0.max(0.0) * CLAMPED_WHITE_BALANCE[0],
+        rgb.1.max(0.0) * CLAMPED_WHITE_BALANCE[1],
+        rgb.2.max(0.0) * CLAMPED_WHITE_BALANCE[2],
+    )
+}
+
+/*
+    Hero wavelength sampling (Wilkie et al. 2014): one uniformly sampled wavelength
+    plus equally spaced companions that share its path
+*/
+
+pub const N_SAMPLES: usize = 4;
+
+#[derive(Clone, Copy)]
+pub struct SampledWavelengths {
+    pub lambda: [f64; N_SAMPLES],
+    pdf: [f64; N_SAMPLES],
+}
+
+impl SampledWavelengths {
+
+    pub fn sample_hero() -> Self {
+        let hero = sample_wavelength();
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let mut lambda = [hero; N_SAMPLES];
+        for (i, l) in lambda.iter_mut().enumerate() {
+            *l = LAMBDA_MIN + (hero - LAMBDA_MIN + i as f64 * range / N_SAMPLES as f64) % range;
+        }
+        Self{lambda, pdf: [1.0 / range; N_SAMPLES]}
+    }
+
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    // Drops the companion wavelengths, e.g. after a wavelength-dependent refraction
+    pub fn terminate_secondary(&mut self) {
+        if self.is_secondary_terminated() {
+            return
+        }
+        self.pdf[0] /= N_SAMPLES as f64;
+        for p in self.pdf.iter_mut().skip(1) {
+            *p = 0.0;
+        }
+    }
+
+    pub fn is_secondary_terminated(&self) -> bool {
+        self.pdf.iter().skip(1).all(|p| *p == 0.0)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct SampledSpectrum(pub [f64; N_SAMPLES]);
+
+impl SampledSpectrum {
+
+    pub fn constant(v: f64) -> Self {
+        SampledSpectrum([v; N_SAMPLES])
+    }
+
+    pub fn from_fn(lambdas: &SampledWavelengths, f: impl Fn(f64) -> f64) -> Self {
+        let mut values = [0.0; N_SAMPLES];
+        for (v, l) in values.iter_mut().zip(lambdas.lambda.iter()) {
+            *v = f(*l);
+        }
+        SampledSpectrum(values)
+    }
+
+    // Uplifts an RGB reflectance (or relative emission) to a smooth spectrum
+    pub fn from_rgb(rgb: Color, lambdas: &SampledWavelengths) -> Self {
+        Self::from_fn(lambdas, |l| rgb_to_spectrum(rgb, l))
+    }
+
+    // Film response: integrates the samples against the CIE matching functions and
+    // converts to linear sRGB, so that a constant spectrum of 1 maps to white.
+    pub fn to_rgb(self, lambdas: &SampledWavelengths) -> Color {
+        let uniform_pdf = 1.0 / (LAMBDA_MAX - LAMBDA_MIN);
+        let mut xyz = Vec3(0.0, 0.0, 0.0);
+        for i in 0..N_SAMPLES {
+            if lambdas.pdf[i] == 0.0 {
+                continue
+            }
+            xyz += (self.0[i] * uniform_pdf / lambdas.pdf[i]) * cie_xyz(lambdas.lambda[i]);
+        }
+        xyz_to_linear_srgb(xyz / N_SAMPLES as f64) * white_balance()
+    }
+}
+
+impl ops::Mul for SampledSpectrum {
+    type Output = SampledSpectrum;
+
+    fn mul(self, rhs: SampledSpectrum) -> SampledSpectrum {
+        let mut values = self.0;
+        for (v, r) in values.iter_mut().zip(rhs.0.iter()) {
+            *v *= r;
+        }
+        SampledSpectrum(values)
+    }
+}
+
+impl ops::Add for SampledSpectrum {
+    type Output = SampledSpectrum;
+
+    fn add(self, rhs: SampledSpectrum) -> SampledSpectrum {
+        let mut values = self.0;
+        for (v, r) in values.iter_mut().zip(rhs.0.iter()) {
+            *v += r;
+        }
+        SampledSpectrum(values)
+    }
+}
+
+/*
+    RGB to spectrum conversion (Smits 1999, "An RGB to Spectrum Conversion for Reflectances")
+*/
+
+const SMITS_BINS: usize = 10;
+const SMITS_MIN: f64 = 380.0;
+const SMITS_MAX: f64 = 720.0;
+
+const SMITS_WHITE: [f64; SMITS_BINS] = [1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000];
+const SMITS_CYAN: [f64; SMITS_BINS] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000];
+const SMITS_MAGENTA: [f64; SMITS_BINS] = [1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959];
+const SMITS_YELLOW: [f64; SMITS_BINS] = [0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840];
+const SMITS_RED: [f64; SMITS_BINS] = [0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149];
+const SMITS_GREEN: [f64; SMITS_BINS] = [0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025];
+const SMITS_BLUE: [f64; SMITS_BINS] = [1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496];
+
+fn smits_basis(table: &[f64; SMITS_BINS], lambda: f64) -> f64 {
+    let x = (lambda - SMITS_MIN) / (SMITS_MAX - SMITS_MIN) * (SMITS_BINS - 1) as f64;
+    if x <= 0.0 {
+        return table[0]
+    }
+    if x >= (SMITS_BINS - 1) as f64 {
+        return table[SMITS_BINS - 1]
+    }
+    let i = x as usize;
+    let f = x - i as f64;
+    table[i] * (1.0 - f) + table[i + 1] * f
+}
+
+pub fn rgb_to_spectrum(rgb: Color, lambda: f64) -> f64 {
+    let (r, g, b) = (rgb.0, rgb.1, rgb.2);
+    let basis = |t: &[f64; SMITS_BINS]| smits_basis(t, lambda);
+    if r <= g && r <= b {
+        let mut v = r * basis(&SMITS_WHITE);
+        if g <= b {
+            v += (g - r) * basis(&SMITS_CYAN) + (b - g) * basis(&SMITS_BLUE);
+        } else {
+            v += (b - r) * basis(&SMITS_CYAN) + (g - b) * basis(&SMITS_GREEN);
+        }
+        v
+    } else if g <= r && g <= b {
+        let mut v = g * basis(&SMITS_WHITE);
+        if r <= b {
+            v += (r - g) * basis(&SMITS_MAGENTA) + (b - r) * basis(&SMITS_BLUE);
+        } else {
+            v += (b - g) * basis(&SMITS_MAGENTA) + (r - b) * basis(&SMITS_RED);
+        }
+        v
+    } else {
+        let mut v = b * basis(&SMITS_WHITE);
+        if r <= g {
+            v += (r - b) * basis(&SMITS_YELLOW) + (g - r) * basis(&SMITS_GREEN);
+        } else {
+            v += (g - b) * basis(&SMITS_YELLOW) + (r - g) * basis(&SMITS_RED);
+        }
+        v
+    }
+}
+
+/*
+    Light emission, given either as RGB or as a physical spectrum
+*/
+
+#[derive(Clone, Copy)]
+pub enum Emission {
+    Rgb(Color),
+    // Planck's law normalized to 1 at its peak, scaled by intensity
+    Blackbody{temperature: f64, intensity: f64},
+}
+
+fn blackbody(lambda: f64, temperature: f64) -> f64 {
+    let c = 299792458.0;
+    let h = 6.62606957e-34;
+    let kb = 1.3806488e-23;
+    let planck = |lambda_nm: f64| {
+        let l = lambda_nm * 1e-9;
+        (2.0 * h * c * c) / (l.powi(5) * ((h * c / (l * kb * temperature)).exp() - 1.0))
+    };
+    let lambda_peak = 2.8977721e-3 / temperature * 1e9;
+    planck(lambda) / planck(lambda_peak)
+}
+
+impl Emission {
+
+    pub fn rgb(&self) -> Color {
+        match *self {
+            Emission::Rgb(c) => c,
+            Emission::Blackbody{temperature, intensity} => {
+                let steps = 80;
+                let mut sum = Vec3(0.0, 0.0, 0.0);
+                for i in 0..steps {
+                    let lambda = LAMBDA_MIN + (i as f64 + 0.5) * (LAMBDA_MAX - LAMBDA_MIN) / steps as f64;
+                    sum += blackbody(lambda, temperature) * wavelength_to_rgb(lambda);
+                }
+                intensity * sum / steps as f64
+            }
+        }
+    }
+
+    pub fn sample(&self, lambdas: &SampledWavelengths) -> SampledSpectrum {
+        match *self {
+            Emission::Rgb(c) => SampledSpectrum::from_rgb(c, lambdas),
+            Emission::Blackbody{temperature, intensity} => {
+                SampledSpectrum::from_fn(lambdas, |l| intensity * blackbody(l, temperature))
+            }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::rc::{Rc};
+use std::fs;
+use std::io;
+
+use crate::vec3::{Vec3, Point, Color};
+use crate::perlin::{Perlin};
+use crate::png;
+
+/*
+    Textures: colours that vary over a surface
+*/
+
+pub trait Texture {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color;
+}
+
+pub struct SolidColor {
+    pub color: Color,
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point) -> Color {
+        self.color
+    }
+}
+
+// Alternates between two textures on a 3D grid with cells of size 1 / scale
+pub struct CheckerTexture {
+    pub odd: Rc<dyn Texture>,
+    pub even: Rc<dyn Texture>,
+    pub scale: f64,
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color {
+        let cell = (self.scale * p.0).floor() + (self.scale * p.1).floor() + (self.scale * p.2).floor();
+        if cell.rem_euclid(2.0) < 1.0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+// Grey Perlin turbulence, usable as a colour or as a bump map height
+pub struct NoiseTexture {
+    pub noise: Perlin,
+    pub scale: f64,
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point) -> Color {
+        let n = self.noise.turbulence(&(self.scale * *p), 7);
+        Vec3(n, n, n)
+    }
+}
+
+// An image indexed by (u, v), with u going right and v going up. Values are returned as stored,
+// in [0, 1], so the same texture type serves colour and normal maps.
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl ImageTexture {
+
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(pixels.len(), width * height, "image needs width * height pixels");
+        Self{width, height, pixels}
+    }
+
+    // Reads a PNG image, or a binary (P6) or plain (P3) PPM image
+    pub fn load(path: &str) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, message));
+        if data.starts_with(&png::SIGNATURE) {
+            let (width, height, pixels, _) = png::decode(&data).map_err(|e| invalid(&e))?;
+            return Ok(Self::from_pixels(width, height, pixels))
+        }
+
+        // Header: magic, width, height and maxval, separated by whitespace and comments
+        let mut pos = 0;
+        let mut header = Vec::new();
+        while header.len() < 4 {
+            while pos < data.len() && (data[pos].is_ascii_whitespace() || data[pos] == b'#') {
+                if data[pos] == b'#' {
+                    while pos < data.len() && data[pos] != b'\n' {
+                        pos += 1;
+                    }
+                } else {
+                    pos += 1;
+                }
+            }
+            let start = pos;
+            while pos < data.len() && !data[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            if start == pos {
+                return Err(invalid("truncated header"))
+            }
+            header.push(String::from_utf8_lossy(&data[start..pos]).into_owned());
+        }
+        let number = |s: &str| s.parse::<usize>().map_err(|_| invalid("bad header value"));
+        let (width, height, maxval) = (number(&header[1])?, number(&header[2])?, number(&header[3])?);
+        if maxval == 0 || maxval > 255 {
+            return Err(invalid("only 8-bit images are supported"))
+        }
+        // The header is untrusted, so the sample count must not overflow
+        let count = width.checked_mul(height).and_then(|n| n.checked_mul(3)).ok_or_else(|| invalid("image dimensions are too large"))?;
+
+        let samples: Vec<f64> = match header[0].as_str() {
+            "P6" => pos.checked_add(1 + count).and_then(|end| data.get(pos + 1..end))
+                .ok_or_else(|| invalid("truncated pixel data"))?
+                .iter().map(|b| *b as f64 / maxval as f64).collect(),
+            "P3" => String::from_utf8_lossy(&data[pos..]).split_ascii_whitespace()
+                .take(count)
+                .map(|s| s.parse::<f64>().map(|v| v / maxval as f64).map_err(|_| invalid("bad pixel value")))
+                .collect::<io::Result<Vec<f64>>>()?,
+            _ => return Err(invalid("not a P3 or P6 PPM image")),
+        };
+        if samples.len() != count {
+            return Err(invalid("truncated pixel data"))
+        }
+        let pixels = samples.chunks(3).map(|c| Vec3(c[0], c[1], c[2])).collect();
+        Ok(Self{width, height, pixels})
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point) -> Color {
+        if self.pixels.is_empty() {
+            return Vec3(0.0, 1.0, 1.0)
+        }
+        // Wraps around, so textures tile
+        let i = ((u.rem_euclid(1.0) * self.width as f64) as usize).min(self.width - 1);
+        let j = (((1.0 - v.rem_euclid(1.0)) * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[j * self.width + i]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ppm_with_overflowing_dimensions_is_rejected() {
+        let path = std::env::temp_dir().join("chapter13_huge.ppm");
+        fs::write(&path, b"P6\n18446744073709551615 18446744073709551615\n255\nabc").unwrap();
+        let err = ImageTexture::load(path.to_str().unwrap()).err().expect("should fail");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("too large"));
+    }
+}
@@ -0,0 +1,400 @@
+use std::ops;
+use std::rc::{Rc};
+
+use crate::vec3::{Vec3, Point};
+use crate::ray::{Ray};
+use crate::aabb::{Aabb};
+use crate::onb::{Onb};
+use crate::hittable::{HitRecord, Hittable};
+
+/*
+    Affine transforms and transformed instances of shared objects
+*/
+
+// Affine transform, row-major with a bottom row of 0 0 0 1; the columns of the upper 3x3 are
+// the transformed axes
+#[derive(Clone, Copy)]
+pub struct Mat4(pub [[f64; 4]; 4]);
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4([[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]]);
+
+    // Matrix stored column by column, as in glTF
+    pub fn from_columns(a: &[f64]) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (r, row) in m.iter_mut().enumerate() {
+            for (c, value) in row.iter_mut().enumerate() {
+                *value = a[c * 4 + r];
+            }
+        }
+        Mat4(m)
+    }
+
+    // Upper 3x3 from three axis columns, plus a translation
+    fn from_axes(x: Vec3, y: Vec3, z: Vec3, t: Vec3) -> Mat4 {
+        Mat4([
+            [x.0, y.0, z.0, t.0],
+            [x.1, y.1, z.1, t.1],
+            [x.2, y.2, z.2, t.2],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn translation(t: Vec3) -> Mat4 {
+        Mat4::from_axes(Vec3(1.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0), Vec3(0.0, 0.0, 1.0), t)
+    }
+
+    pub fn scaling(s: Vec3) -> Mat4 {
+        Mat4::from_axes(Vec3(s.0, 0.0, 0.0), Vec3(0.0, s.1, 0.0), Vec3(0.0, 0.0, s.2), Vec3(0.0, 0.0, 0.0))
+    }
+
+    // Rotation by an angle in degrees, counterclockwise looking down the axis towards the origin
+    pub fn rotation(axis: Vec3, degrees: f64) -> Mat4 {
+        let a = axis.unit_vector();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        // Rodrigues: each basis vector e rotates to e cos + (a x e) sin + a (a . e)(1 - cos)
+        let rotate = |e: Vec3| cos * e + sin * a.cross(e) + (a.dot(e) * (1.0 - cos)) * a;
+        Mat4::from_axes(rotate(Vec3(1.0, 0.0, 0.0)), rotate(Vec3(0.0, 1.0, 0.0)), rotate(Vec3(0.0, 0.0, 1.0)), Vec3(0.0, 0.0, 0.0))
+    }
+
+    // Rotation by the quaternion x i + y j + z k + w, which is normalized first
+    pub fn quaternion(x: f64, y: f64, z: f64, w: f64) -> Mat4 {
+        let n = (x * x + y * y + z * z + w * w).sqrt();
+        let (x, y, z, w) = (x / n, y / n, z / n, w / n);
+        Mat4([
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - z * w), 2.0 * (x * z + y * w), 0.0],
+            [2.0 * (x * y + z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - x * w), 0.0],
+            [2.0 * (x * z - y * w), 2.0 * (y * z + x * w), 1.0 - 2.0 * (x * x + y * y), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn point(&self, p: Point) -> Point {
+        let m = &self.0;
+        Vec3(
+            m[0][0] * p.0 + m[0][1] * p.1 + m[0][2] * p.2 + m[0][3],
+            m[1][0] * p.0 + m[1][1] * p.1 + m[1][2] * p.2 + m[1][3],
+            m[2][0] * p.0 + m[2][1] * p.1 + m[2][2] * p.2 + m[2][3],
+        )
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        v.0 * self.column(0) + v.1 * self.column(1) + v.2 * self.column(2)
+    }
+
+    pub fn column(&self, c: usize) -> Vec3 {
+        Vec3(self.0[0][c], self.0[1][c], self.0[2][c])
+    }
+
+    // Normals transform by the inverse transpose. The cofactor matrix is that times the determinant,
+    // so it is used with the determinant's sign, which also says whether the winding flips.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        let (c0, c1, c2) = (self.column(0), self.column(1), self.column(2));
+        let sign = self.determinant().signum();
+        sign * (n.0 * c1.cross(c2) + n.1 * c2.cross(c0) + n.2 * c0.cross(c1))
+    }
+
+    pub fn determinant(&self) -> f64 {
+        self.column(0).dot(self.column(1).cross(self.column(2)))
+    }
+
+    // None when the transform collapses space onto a plane, line or point
+    pub fn inverse(&self) -> Option<Mat4> {
+        let det = self.determinant();
+        if det == 0.0 || !det.is_finite() {
+            return None
+        }
+        // Rows of the inverse of the upper 3x3 are the cross products of its columns over det
+        let (c0, c1, c2) = (self.column(0), self.column(1), self.column(2));
+        let rows = [c1.cross(c2) / det, c2.cross(c0) / det, c0.cross(c1) / det];
+        let t = self.column(3);
+        let mut m = Mat4::IDENTITY.0;
+        for (r, row) in rows.iter().enumerate() {
+            m[r] = [row.0, row.1, row.2, -row.dot(t)];
+        }
+        Some(Mat4(m))
+    }
+}
+
+impl ops::Mul<Mat4> for Mat4 {
+    type Output = Mat4;
+
+    // Applies rhs first
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (r, row) in m.iter_mut().enumerate() {
+            for (c, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.0[r][k] * rhs.0[k][c]).sum();
+            }
+        }
+        Mat4(m)
+    }
+}
+
+// Invertible affine transform, kept together with its inverse
+#[derive(Clone, Copy)]
+pub struct Transform {
+    pub matrix: Mat4,
+    pub inverse: Mat4,
+}
+
+impl Transform {
+    // None for a singular matrix, which no instance can be placed with
+    pub fn new(matrix: Mat4) -> Option<Self> {
+        let inverse = matrix.inverse()?;
+        Some(Self{matrix, inverse})
+    }
+
+    pub fn translate(t: Vec3) -> Self {
+        Self{matrix: Mat4::translation(t), inverse: Mat4::translation(-t)}
+    }
+
+    // Panics when a factor is zero; scales read from files go through try_scale
+    pub fn scale(s: Vec3) -> Self {
+        assert!(s.0 != 0.0 && s.1 != 0.0 && s.2 != 0.0, "scale factors must be nonzero");
+        Self{matrix: Mat4::scaling(s), inverse: Mat4::scaling(Vec3(1.0 / s.0, 1.0 / s.1, 1.0 / s.2))}
+    }
+
+    // None when a factor is zero
+    pub fn try_scale(s: Vec3) -> Option<Self> {
+        Self::new(Mat4::scaling(s))
+    }
+
+    pub fn rotate(axis: Vec3, degrees: f64) -> Self {
+        Self{matrix: Mat4::rotation(axis, degrees), inverse: Mat4::rotation(axis, -degrees)}
+    }
+
+    pub fn rotate_quaternion(x: f64, y: f64, z: f64, w: f64) -> Self {
+        Self{matrix: Mat4::quaternion(x, y, z, w), inverse: Mat4::quaternion(-x, -y, -z, w)}
+    }
+
+    // Box around the transformed corners of b
+    pub fn bounding_box(&self, b: &Aabb) -> Aabb {
+        let corners: Vec<Point> = (0..8).map(|i| self.matrix.point(Vec3(
+            if i & 1 == 0 {b.minimum.0} else {b.maximum.0},
+            if i & 2 == 0 {b.minimum.1} else {b.maximum.1},
+            if i & 4 == 0 {b.minimum.2} else {b.maximum.2},
+        ))).collect();
+        Aabb::from_points(&corners)
+    }
+}
+
+impl ops::Mul<Transform> for Transform {
+    type Output = Transform;
+
+    // Applies rhs first, so translate * rotate * scale scales, then rotates, then translates
+    fn mul(self, rhs: Transform) -> Transform {
+        Transform{matrix: self.matrix * rhs.matrix, inverse: rhs.inverse * self.inverse}
+    }
+}
+
+// An object placed by a transform. The object is shared, so any number of instances can reuse
+// one mesh. Rays are carried into the object's space unnormalized, so t is the same in both.
+pub struct Transformed {
+    pub object: Rc<dyn Hittable>,
+    pub transform: Transform,
+}
+
+// Hit on an object placed by a transform, with the record brought back into world space
+fn hit_transformed(object: &dyn Hittable, transform: &Transform, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    let inverse = &transform.inverse;
+    let local = Ray{origin: inverse.point(r.origin), direction: inverse.vector(r.direction), ..*r};
+    let rec = object.hit(&local, t_min, t_max)?;
+
+    // The normals stay on the ray's side, since n . d is unchanged by the inverse transpose
+    let m = &transform.matrix;
+    let normal = m.normal(rec.normal).unit_vector();
+    Some(HitRecord{
+        p: r.at(rec.t),
+        normal,
+        geometric_normal: m.normal(rec.geometric_normal).unit_vector(),
+        tangent: Onb::from_normal_tangent(normal, m.vector(rec.tangent)).u,
+        ..rec
+    })
+}
+
+impl Hittable for Transformed {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        hit_transformed(self.object.as_ref(), &self.transform, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box().map(|b| self.transform.bounding_box(&b))
+    }
+}
+
+// Quaternion (x, y, z, w) for a rotation by an angle in degrees about an axis
+pub fn axis_angle(axis: Vec3, degrees: f64) -> [f64; 4] {
+    let a = axis.unit_vector();
+    let (sin, cos) = (degrees.to_radians() / 2.0).sin_cos();
+    [a.0 * sin, a.1 * sin, a.2 * sin, cos]
+}
+
+// Pose at a point in time: scaled, then rotated by a quaternion (x, y, z, w), then translated
+#[derive(Clone, Copy)]
+pub struct Keyframe {
+    pub time: f64,
+    pub translation: Vec3,
+    pub rotation: [f64; 4],
+    pub scale: Vec3,
+}
+
+impl Keyframe {
+    // None when a scale factor is zero
+    fn transform(&self) -> Option<Transform> {
+        let [x, y, z, w] = self.rotation;
+        Some(Transform::translate(self.translation) * Transform::rotate_quaternion(x, y, z, w) * Transform::try_scale(self.scale)?)
+    }
+
+    // Translation and scale are interpolated linearly and rotation along the shorter arc
+    fn lerp(a: &Keyframe, b: &Keyframe, f: f64) -> Keyframe {
+        let (qa, mut qb) = (a.rotation, b.rotation);
+        let mut cos = (0..4).map(|i| qa[i] * qb[i]).sum::<f64>();
+        if cos < 0.0 {
+            qb = [-qb[0], -qb[1], -qb[2], -qb[3]];
+            cos = -cos;
+        }
+        // Nearly equal rotations divide by almost nothing in slerp, and lerp is as good there
+        let (wa, wb) = if cos > 0.9995 {
+            (1.0 - f, f)
+        } else {
+            let theta = cos.acos();
+            (((1.0 - f) * theta).sin() / theta.sin(), (f * theta).sin() / theta.sin())
+        };
+        Keyframe{
+            time: a.time + f * (b.time - a.time),
+            translation: a.translation + f * (b.translation - a.translation),
+            rotation: [wa * qa[0] + wb * qb[0], wa * qa[1] + wb * qb[1], wa * qa[2] + wb * qb[2], wa * qa[3] + wb * qb[3]],
+            scale: a.scale + f * (b.scale - a.scale),
+        }
+    }
+}
+
+// An object moving through a sequence of keyframes, sorted by time, posed at each ray's time.
+// Before the first keyframe and after the last the object holds still. Where a pose scales it to
+// nothing it is missed, so objects can grow from or shrink to a point.
+pub struct Animated {
+    object: Rc<dyn Hittable>,
+    keyframes: Vec<Keyframe>,
+}
+
+impl Animated {
+    pub fn new(object: Rc<dyn Hittable>, keyframes: Vec<Keyframe>) -> Self {
+        assert!(!keyframes.is_empty(), "Animated::new needs at least one keyframe");
+        assert!(keyframes.windows(2).all(|k| k[0].time <= k[1].time), "keyframes must be sorted by time");
+        Self{object, keyframes}
+    }
+
+    fn at(&self, time: f64) -> Keyframe {
+        let next = self.keyframes.iter().position(|k| k.time > time);
+        match next {
+            Some(0) => self.keyframes[0],
+            None => self.keyframes[self.keyframes.len() - 1],
+            Some(i) => {
+                let (a, b) = (&self.keyframes[i - 1], &self.keyframes[i]);
+                Keyframe::lerp(a, b, (time - a.time) / (b.time - a.time))
+            },
+        }
+    }
+}
+
+impl Hittable for Animated {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let transform = self.at(r.time).transform()?;
+        hit_transformed(self.object.as_ref(), &transform, r, t_min, t_max)
+    }
+
+    // Rotation between keyframes can swing the object anywhere around its pivot, so this bounds
+    // the object's bounding sphere, scaled by the largest scale factor, around the path of the
+    // translation, which is the segments between the keyframe translations
+    fn bounding_box(&self) -> Option<Aabb> {
+        let b = self.object.bounding_box()?;
+        let center = b.centroid();
+        let reach = center.length() + (b.maximum - center).length();
+        let scale = self.keyframes.iter().map(|k| k.scale.0.abs().max(k.scale.1.abs()).max(k.scale.2.abs())).fold(0.0, f64::max);
+        let e = Vec3(scale * reach, scale * reach, scale * reach);
+        let corners: Vec<Point> = self.keyframes.iter().flat_map(|k| vec![k.translation - e, k.translation + e]).collect();
+        Some(Aabb::from_points(&corners))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::{Sphere};
+    use crate::material::{Lambertian};
+
+    fn assert_identity(m: Mat4) {
+        for (r, row) in m.0.iter().enumerate() {
+            for (c, &value) in row.iter().enumerate() {
+                let expected = if r == c {1.0} else {0.0};
+                assert!((value - expected).abs() < 1e-12, "entry {} {} is {}", r, c, value);
+            }
+        }
+    }
+
+    fn transforms() -> Vec<Transform> {
+        let [x, y, z, w] = axis_angle(Vec3(-1.0, 2.0, 0.5), 123.0);
+        vec![
+            Transform::rotate(Vec3(1.0, 1.0, 0.0), 40.0),
+            Transform::rotate_quaternion(x, y, z, w),
+            Transform::scale(Vec3(1.2, -0.4, 3.0)),
+            Transform::translate(Vec3(1.0, 2.0, 3.0)) * Transform::rotate(Vec3(0.0, 0.0, 1.0), -75.0) * Transform::scale(Vec3(2.0, 0.5, 0.25)),
+        ]
+    }
+
+    #[test]
+    fn matrix_times_inverse_is_identity() {
+        for t in transforms() {
+            assert_identity(t.matrix * t.inverse);
+            assert_identity(t.inverse * t.matrix);
+            assert_identity(t.matrix * t.matrix.inverse().unwrap());
+        }
+    }
+
+    #[test]
+    fn normal_is_the_inverse_transpose() {
+        let n = Vec3(0.3, -0.8, 0.5);
+        for t in transforms() {
+            let inverse = t.matrix.inverse().unwrap();
+            let transposed = Vec3(inverse.column(0).dot(n), inverse.column(1).dot(n), inverse.column(2).dot(n));
+            let normal = t.matrix.normal(n);
+            // Equal up to a positive factor, so the normal stays on the same side
+            assert!(normal.cross(transposed).length() < 1e-12 * normal.length() * transposed.length());
+            assert!(normal.dot(transposed) > 0.0);
+            // and still perpendicular to transformed tangents
+            let tangent = Vec3(0.8, 0.3, 0.0);
+            assert!(tangent.dot(n).abs() < 1e-12);
+            assert!(normal.dot(t.matrix.vector(tangent)).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn animation_from_zero_scale_misses_until_it_grows() {
+        let sphere = Rc::new(Sphere{center: Vec3(0.0, 0.0, 0.0), radius: 1.0, material: Rc::new(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)})});
+        let pose = |time: f64, s: f64| Keyframe{time, translation: Vec3(0.0, 0.0, 0.0), rotation: [0.0, 0.0, 0.0, 1.0], scale: Vec3(s, s, s)};
+        let animated = Animated::new(sphere, vec![pose(0.0, 0.0), pose(1.0, 2.0)]);
+        let ray = |time: f64| Ray{origin: Vec3(0.0, 0.0, -5.0), direction: Vec3(0.0, 0.0, 1.0), wavelength: None, time};
+
+        assert!(animated.hit(&ray(-1.0), 0.0, f64::INFINITY).is_none());
+        assert!(animated.hit(&ray(0.0), 0.0, f64::INFINITY).is_none());
+        let rec = animated.hit(&ray(0.5), 0.0, f64::INFINITY).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-9);
+        let rec = animated.hit(&ray(2.0), 0.0, f64::INFINITY).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn singular_transforms_are_rejected() {
+        assert!(Transform::try_scale(Vec3(1.0, 0.0, 1.0)).is_none());
+        assert!(Transform::new(Mat4::from_axes(Vec3(1.0, 0.0, 0.0), Vec3(2.0, 0.0, 0.0), Vec3(0.0, 0.0, 1.0), Vec3(0.0, 0.0, 0.0))).is_none());
+        assert!(Transform::new(Mat4::scaling(Vec3(f64::NAN, 1.0, 1.0))).is_none());
+        assert!(Transform::new(Mat4::IDENTITY).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "scale factors must be nonzero")]
+    fn zero_scale_panics() {
+        Transform::scale(Vec3(1.0, 1.0, 0.0));
+    }
+}
@@ -0,0 +1,262 @@
+use std::rc::{Rc};
+
+use crate::ray::{Ray};
+use crate::vec3::{Vec3, Point, Color};
+use crate::aabb::{Aabb};
+use crate::bvh::{BvhNode};
+use crate::material::{Material};
+use crate::hittable::{HitRecord, Hittable};
+
+/*
+    Triangles and indexed triangle meshes
+*/
+
+// Watertight ray-triangle intersection (Woop, Benthin and Wald 2013). The vertices are moved into
+// a frame where the ray runs along +z from the origin, so neighbouring triangles evaluate their
+// shared edge identically and rays cannot slip through the seams of a mesh.
+// Returns t and the barycentric weights of the three vertices.
+fn intersect(r: &Ray, p: [Point; 3], t_min: f64, t_max: f64) -> Option<(f64, [f64; 3])> {
+    let d = r.direction;
+    let kz = if d.0.abs() > d.1.abs() {
+        if d.0.abs() > d.2.abs() {0} else {2}
+    } else if d.1.abs() > d.2.abs() {1} else {2};
+    let (kx, ky) = if d[kz] < 0.0 {((kz + 2) % 3, (kz + 1) % 3)} else {((kz + 1) % 3, (kz + 2) % 3)};
+
+    // Shear that takes the ray direction to +z
+    let sx = d[kx] / d[kz];
+    let sy = d[ky] / d[kz];
+    let sz = 1.0 / d[kz];
+
+    let a = p[0] - r.origin;
+    let b = p[1] - r.origin;
+    let c = p[2] - r.origin;
+    let (ax, ay) = (a[kx] - sx * a[kz], a[ky] - sy * a[kz]);
+    let (bx, by) = (b[kx] - sx * b[kz], b[ky] - sy * b[kz]);
+    let (cx, cy) = (c[kx] - sx * c[kz], c[ky] - sy * c[kz]);
+
+    // Edge functions, each weighting the vertex opposite its edge
+    let u = cx * by - cy * bx;
+    let v = ax * cy - ay * cx;
+    let w = bx * ay - by * ax;
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return None
+    }
+    let det = u + v + w;
+    if det == 0.0 {
+        return None
+    }
+
+    let t = (u * a[kz] + v * b[kz] + w * c[kz]) * sz / det;
+    if !(t_min..=t_max).contains(&t) {
+        return None
+    }
+    Some((t, [u / det, v / det, w / det]))
+}
+
+// Optional per-vertex attributes of one triangle
+#[derive(Default)]
+struct Attributes {
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    colors: Option<[Color; 3]>,
+}
+
+// Builds the hit record for a triangle, interpolating whichever vertex attributes it has.
+// Without UVs the barycentric coordinates of the second and third vertex are used.
+fn hit_triangle(r: &Ray, t_min: f64, t_max: f64, p: [Point; 3], attributes: Attributes, material: &Rc<dyn Material>) -> Option<HitRecord> {
+    let (t, b) = intersect(r, p, t_min, t_max)?;
+    let point = b[0] * p[0] + b[1] * p[1] + b[2] * p[2];
+    let outward_normal = (p[1] - p[0]).cross(p[2] - p[0]).unit_vector();
+
+    let (uv, dpdu) = match attributes.uvs {
+        Some(uv) => {
+            let interpolated = (
+                b[0] * uv[0].0 + b[1] * uv[1].0 + b[2] * uv[2].0,
+                b[0] * uv[0].1 + b[1] * uv[1].1 + b[2] * uv[2].1,
+            );
+            // Solve for the surface direction in which u increases
+            let (du02, dv02) = (uv[0].0 - uv[2].0, uv[0].1 - uv[2].1);
+            let (du12, dv12) = (uv[1].0 - uv[2].0, uv[1].1 - uv[2].1);
+            let det = du02 * dv12 - dv02 * du12;
+            let dpdu = if det.abs() < 1e-12 {
+                p[1] - p[0]
+            } else {
+                (dv12 * (p[0] - p[2]) - dv02 * (p[1] - p[2])) / det
+            };
+            (interpolated, dpdu)
+        },
+        None => ((b[1], b[2]), p[1] - p[0]),
+    };
+
+    let mut rec = HitRecord::new(point, t, r, outward_normal, uv, material.clone()).with_tangent(dpdu);
+    if let Some(n) = attributes.normals {
+        rec = rec.with_shading_normal(b[0] * n[0] + b[1] * n[1] + b[2] * n[2]);
+    }
+    rec.vertex_color = attributes.colors.map(|c| b[0] * c[0] + b[1] * c[1] + b[2] * c[2]);
+    Some(rec)
+}
+
+fn triangle_box(p: [Point; 3]) -> Aabb {
+    Aabb::from_points(&p)
+}
+
+// A single flat-shaded triangle; the front face is the side from which v0, v1, v2 run counter-clockwise
+pub struct Triangle {
+    pub v0: Point,
+    pub v1: Point,
+    pub v2: Point,
+    pub material: Rc<dyn Material>,
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        hit_triangle(r, t_min, t_max, [self.v0, self.v1, self.v2], Attributes::default(), &self.material)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(triangle_box([self.v0, self.v1, self.v2]))
+    }
+}
+
+// Indexed triangle geometry. Vertices are shared between triangles by index; normals, uvs and
+// colours, when present, have one entry per position.
+pub struct Mesh {
+    pub positions: Vec<Point>,
+    pub normals: Option<Vec<Vec3>>,
+    pub uvs: Option<Vec<(f64, f64)>>,
+    pub colors: Option<Vec<Color>>,
+    pub triangles: Vec<[usize; 3]>,
+}
+
+impl Mesh {
+    fn vertices<T: Copy>(values: &[T], [i, j, k]: [usize; 3]) -> [T; 3] {
+        [values[i], values[j], values[k]]
+    }
+}
+
+struct MeshTriangle {
+    mesh: Rc<Mesh>,
+    indices: [usize; 3],
+    material: Rc<dyn Material>,
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mesh = &self.mesh;
+        let p = Mesh::vertices(&mesh.positions, self.indices);
+        let attributes = Attributes{
+            normals: mesh.normals.as_ref().map(|n| Mesh::vertices(n, self.indices)),
+            uvs: mesh.uvs.as_ref().map(|uv| Mesh::vertices(uv, self.indices)),
+            colors: mesh.colors.as_ref().map(|c| Mesh::vertices(c, self.indices)),
+        };
+        hit_triangle(r, t_min, t_max, p, attributes, &self.material)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(triangle_box(Mesh::vertices(&self.mesh.positions, self.indices)))
+    }
+}
+
+// A mesh with one material, smooth shaded when it has normals. Its triangles are kept in a BVH,
+// which is absent when none are left.
+pub struct TriangleMesh {
+    bvh: Option<BvhNode>,
+}
+
+impl TriangleMesh {
+
+    // Panics if an index or the length of the normals, uvs or colours does not match the positions.
+    // Degenerate triangles are dropped.
+    pub fn new(mesh: Mesh, material: Rc<dyn Material>) -> Self {
+        let n = mesh.positions.len();
+        assert!(mesh.normals.as_ref().map_or(true, |v| v.len() == n), "mesh needs one normal per position");
+        assert!(mesh.uvs.as_ref().map_or(true, |v| v.len() == n), "mesh needs one uv per position");
+        assert!(mesh.colors.as_ref().map_or(true, |v| v.len() == n), "mesh needs one colour per position");
+        assert!(mesh.triangles.iter().flatten().all(|&i| i < n), "mesh triangle index out of range");
+
+        let mesh = Rc::new(mesh);
+        let triangles: Vec<Box<dyn Hittable>> = mesh.triangles.iter()
+            .filter(|&&indices| {
+                let p = Mesh::vertices(&mesh.positions, indices);
+                (p[1] - p[0]).cross(p[2] - p[0]).length_squared() > 0.0
+            })
+            .map(|&indices| -> Box<dyn Hittable> {
+                Box::new(MeshTriangle{mesh: mesh.clone(), indices, material: material.clone()})
+            })
+            .collect();
+        let bvh = BvhNode::new(triangles).ok();
+        Self{bvh}
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.bvh.as_ref()?.hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bvh.as_ref()?.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Lambertian};
+
+    const P: [Point; 3] = [Vec3(0.0, 0.0, 0.0), Vec3(1.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0)];
+
+    fn ray(origin: Point, direction: Vec3) -> Ray {
+        Ray{origin, direction, wavelength: None, time: 0.0}
+    }
+
+    #[test]
+    fn hit_inside_gives_t_and_barycentrics() {
+        let (t, b) = intersect(&ray(Vec3(0.25, 0.5, 2.0), Vec3(0.0, 0.0, -1.0)), P, 0.0, f64::INFINITY).unwrap();
+        assert!((t - 2.0).abs() < 1e-12);
+        assert!((b[0] - 0.25).abs() < 1e-12 && (b[1] - 0.25).abs() < 1e-12 && (b[2] - 0.5).abs() < 1e-12);
+
+        // Hits from behind count too, and t must lie in range
+        assert!(intersect(&ray(Vec3(0.25, 0.25, -1.0), Vec3(0.0, 0.0, 1.0)), P, 0.0, f64::INFINITY).is_some());
+        assert!(intersect(&ray(Vec3(0.25, 0.25, 2.0), Vec3(0.0, 0.0, -1.0)), P, 0.0, 1.0).is_none());
+        assert!(intersect(&ray(Vec3(0.75, 0.75, 2.0), Vec3(0.0, 0.0, -1.0)), P, 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn ray_through_a_vertex_hits_it() {
+        for (i, &v) in P.iter().enumerate() {
+            let origin = v + Vec3(0.3, -0.2, 1.0);
+            let (t, b) = intersect(&ray(origin, v - origin), P, 0.0, f64::INFINITY).unwrap();
+            assert!((t - 1.0).abs() < 1e-12);
+            assert!((b[i] - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn shared_edge_is_watertight() {
+        // Two triangles sharing the diagonal of a unit square; rays along the diagonal must hit one
+        let other = [Vec3(1.0, 0.0, 0.0), Vec3(1.0, 1.0, 0.0), Vec3(0.0, 1.0, 0.0)];
+        for i in 1..100 {
+            let s = i as f64 / 100.0;
+            let r = ray(Vec3(s, 1.0 - s, 0.0), Vec3(0.1, 0.3, -1.0));
+            let r = Ray{origin: r.origin - r.direction, ..r};
+            assert!(intersect(&r, P, 0.0, f64::INFINITY).is_some() || intersect(&r, other, 0.0, f64::INFINITY).is_some());
+        }
+    }
+
+    #[test]
+    fn edge_on_and_parallel_rays_miss() {
+        assert!(intersect(&ray(Vec3(-1.0, 0.25, 0.0), Vec3(1.0, 0.0, 0.0)), P, 0.0, f64::INFINITY).is_none());
+        assert!(intersect(&ray(Vec3(-1.0, 0.25, 0.5), Vec3(1.0, 0.0, 0.0)), P, 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn triangle_faces_counter_clockwise() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian{albedo: Vec3(0.5, 0.5, 0.5)});
+        let triangle = Triangle{v0: P[0], v1: P[1], v2: P[2], material};
+        let front = triangle.hit(&ray(Vec3(0.25, 0.25, 1.0), Vec3(0.0, 0.0, -1.0)), 0.0, f64::INFINITY).unwrap();
+        let back = triangle.hit(&ray(Vec3(0.25, 0.25, -1.0), Vec3(0.0, 0.0, 1.0)), 0.0, f64::INFINITY).unwrap();
+        assert!(front.front_face && !back.front_face);
+        assert!((front.normal.2 - 1.0).abs() < 1e-12 && (back.normal.2 + 1.0).abs() < 1e-12);
+    }
+}
@@ -51,6 +51,15 @@ impl Vec3 {
         }
     }
 
+    // Cosine-weighted direction around +z
+    pub fn random_cosine_direction() -> Self {
+        let r1 = get_rand();
+        let r2 = get_rand();
+        let phi = 2.0 * std::f64::consts::PI * r1;
+        let z = (1.0 - r2).sqrt();
+        Vec3(phi.cos() * r2.sqrt(), phi.sin() * r2.sqrt(), z)
+    }
+
     pub fn length(&self) -> f64 {
         self.length_squared().sqrt()
     }
@@ -187,6 +196,19 @@ impl ops::DivAssign<f64> for Vec3 {
     }
 }
 
+impl ops::Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.0,
+            1 => &self.1,
+            2 => &self.2,
+            _ => panic!("Vec3 axis {} out of range", axis),
+        }
+    }
+}
+
 impl fmt::Display for Vec3 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{} {} {}", self.0, self.1, self.2)